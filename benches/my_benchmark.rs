@@ -1,5 +1,5 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use poetry_resolver::resolve;
+use criterion::{criterion_group, criterion_main, Criterion};
+use poetry_resolver::{resolve, Environment, PrereleasePolicy, PypiJsonIndex};
 use std::time::Duration;
 
 fn test_resolve_poetry() {
@@ -19,8 +19,7 @@ fn test_resolve_poetry() {
         ("packaging", ">=20.4,<21"),
         ("virtualenv", ">20.0.26,<21"),
         ("keyring", ">=21.2.0,<22"),
-        // missing extra "filecache"
-        ("cachecontrol", ">=0.12.4,<0.13"),
+        ("cachecontrol[filecache]", ">=0.12.4,<0.13"),
     ], vec![
         ("pytest", ">=5.4.3,<6"),
         ("pre-commit", ">=2.6,<3"),
@@ -31,7 +30,7 @@ fn test_resolve_poetry() {
         ("httpretty", ">=1.0,<2"),
         ("urllib3", "==1.25.10"),
         ("setuptools-rust", ">=0.11.5,<0.12")
-    ]);
+    ], &Environment::default(), &PrereleasePolicy::default(), PypiJsonIndex::default()).unwrap();
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -0,0 +1,266 @@
+use crate::requirement::normalize_name;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Layout of the entries, bumped whenever they change incompatibly
+/// so that entries written by other versions are never read.
+const FORMAT: &str = "v2";
+
+/// Package metadata kept on disk between resolutions.
+///
+/// Entries are grouped by index, so mirrors never share them.
+/// An entry is used as is while it is younger than its time to live,
+/// older entries are revalidated with the `ETag` and `Last-Modified` the index sent along.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataCache {
+    dir: PathBuf,
+    releases_ttl: Duration,
+    requires_dist_ttl: Duration,
+}
+
+impl MetadataCache {
+    /// A cache below `dir`, which is created when the first entry is stored.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        MetadataCache {
+            dir: dir.into(),
+            releases_ttl: Duration::from_secs(10 * 60),
+            requires_dist_ttl: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+
+    /// The cache in the user's cache directory, like `~/.cache/poetry-resolver`,
+    /// or `None` when there is no such directory.
+    pub fn user_default() -> Option<Self> {
+        let dir = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        };
+        Some(MetadataCache::new(dir.join("poetry-resolver")))
+    }
+
+    /// How long the list of releases of a package is used without asking the index.
+    /// New releases show up only after this time.
+    pub fn with_releases_ttl(self, releases_ttl: Duration) -> Self {
+        MetadataCache {
+            releases_ttl,
+            ..self
+        }
+    }
+
+    /// How long the `Requires-Dist` of a release is used without asking the index.
+    /// Indexes practically never change it after upload.
+    pub fn with_requires_dist_ttl(self, requires_dist_ttl: Duration) -> Self {
+        MetadataCache {
+            requires_dist_ttl,
+            ..self
+        }
+    }
+
+    /// The entry with the releases of `package` on the index at `index`,
+    /// along with whatever else the index tells about the whole project.
+    pub(crate) fn project<T>(&self, index: &str, package: &str) -> EntrySlot<T> {
+        EntrySlot::new(
+            self.index_dir(index)
+                .join("projects")
+                .join(format!("{}.json", escape(&normalize_name(package)))),
+            self.releases_ttl,
+        )
+    }
+
+    /// The entry with the `Requires-Dist` of `version` of `package` on the index at `index`,
+    /// with `version` spelled the way the index publishes it.
+    pub(crate) fn requires_dist<T>(
+        &self,
+        index: &str,
+        package: &str,
+        version: &str,
+    ) -> EntrySlot<T> {
+        EntrySlot::new(
+            self.index_dir(index)
+                .join("requires-dist")
+                .join(escape(&normalize_name(package)))
+                .join(format!("{}.json", escape(version))),
+            self.requires_dist_ttl,
+        )
+    }
+
+    fn index_dir(&self, index: &str) -> PathBuf {
+        self.dir.join(FORMAT).join(escape(index))
+    }
+}
+
+/// Replace everything but ASCII alphanumerics, `-`, `_` and `.` with `%XX`,
+/// so that any string is a single, distinct file name.
+fn escape(component: &str) -> String {
+    let mut escaped = String::with_capacity(component.len());
+    for byte in component.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => escaped.push(byte as char),
+            // a leading dot would make `.` and `..` possible
+            b'.' if !escaped.is_empty() => escaped.push('.'),
+            _ => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Metadata as fetched from an index, with what is needed to revalidate it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry<T> {
+    /// Seconds since the unix epoch when the index last confirmed `data`.
+    pub fetched_at: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub data: T,
+}
+
+impl<T> Entry<T> {
+    /// `data` that was just fetched along with the validators of the response.
+    pub fn fetched(data: T, etag: Option<String>, last_modified: Option<String>) -> Self {
+        Entry {
+            fetched_at: now(),
+            etag,
+            last_modified,
+            data,
+        }
+    }
+
+    /// Mark `data` as confirmed by the index just now.
+    pub fn revalidated(self) -> Self {
+        Entry {
+            fetched_at: now(),
+            ..self
+        }
+    }
+}
+
+/// Where a single entry of a [MetadataCache] is kept.
+pub(crate) struct EntrySlot<T> {
+    path: PathBuf,
+    ttl: Duration,
+    data: std::marker::PhantomData<T>,
+}
+
+impl<T> EntrySlot<T> {
+    fn new(path: PathBuf, ttl: Duration) -> Self {
+        EntrySlot {
+            path,
+            ttl,
+            data: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> EntrySlot<T> {
+    /// The stored entry, if there is a readable one.
+    pub fn load(&self) -> Option<Entry<T>> {
+        let content = fs::read(&self.path).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    /// Whether `entry` can be used without revalidating it.
+    pub fn is_fresh(&self, entry: &Entry<T>) -> bool {
+        now().saturating_sub(entry.fetched_at) < self.ttl.as_secs()
+    }
+
+    /// Replace the stored entry with `entry`.
+    ///
+    /// The entry is written to a temporary file first,
+    /// so concurrent resolutions never read a partially written one.
+    pub fn store(&self, entry: &Entry<T>) -> io::Result<()> {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(dir)?;
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(format!(".{}.tmp", std::process::id()));
+        fs::write(&temporary, serde_json::to_vec(entry)?)?;
+        fs::rename(&temporary, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::{escape, Entry, MetadataCache, FORMAT};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    /// An empty directory unique to `test`.
+    fn cache_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("poetry-resolver-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("requests"), "requests");
+        assert_eq!(escape("1.0+local.1"), "1.0%2Blocal.1");
+        assert_eq!(
+            escape("https://pypi.org/pypi"),
+            "https%3A%2F%2Fpypi.org%2Fpypi"
+        );
+        assert_eq!(escape(".."), "%2E.");
+        assert_eq!(escape(""), "");
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let dir = cache_dir("store-and-load");
+        let cache = MetadataCache::new(&dir);
+        let slot = cache.project::<Vec<String>>("https://pypi.org/pypi", "Foo_Bar");
+        assert_eq!(slot.load(), None);
+        let entry = Entry::fetched(vec!["1.0".to_string()], Some("\"abc\"".into()), None);
+        slot.store(&entry).unwrap();
+        assert_eq!(slot.load(), Some(entry.clone()));
+        assert!(slot.is_fresh(&entry));
+        // names are normalized, indexes are kept apart
+        assert_eq!(
+            cache
+                .project::<Vec<String>>("https://pypi.org/pypi", "foo-bar")
+                .load(),
+            Some(entry)
+        );
+        assert_eq!(
+            cache
+                .project::<Vec<String>>("https://mirror/pypi", "foo-bar")
+                .load(),
+            None
+        );
+
+        let slot = cache.requires_dist::<Vec<String>>("https://pypi.org/pypi", "foo-bar", "1.0");
+        assert_eq!(slot.load(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expiry_and_unreadable_entries() {
+        let dir = cache_dir("expiry");
+        let cache = MetadataCache::new(&dir).with_releases_ttl(Duration::from_secs(60));
+        let slot = cache.project::<Vec<String>>("index", "foo");
+        let mut entry = Entry::fetched(vec![], None, Some("yesterday".into()));
+        assert!(slot.is_fresh(&entry));
+        entry.fetched_at -= 120;
+        assert!(!slot.is_fresh(&entry));
+        assert!(slot.is_fresh(&entry.revalidated()));
+
+        let path = dir
+            .join(FORMAT)
+            .join("index")
+            .join("projects")
+            .join("foo.json");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(slot.load(), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
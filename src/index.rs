@@ -0,0 +1,762 @@
+use crate::cache::{Entry, EntrySlot, MetadataCache};
+use crate::requirement::normalize_name;
+use crate::version::PEP440Version;
+use reqwest::header::{HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+/// A distribution file of a release, like a wheel or a source distribution.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReleaseFile {
+    /// `Requires-Python` of the file, if it declares one.
+    pub requires_python: Option<String>,
+    /// Whether the file was yanked, see [PEP 592](https://www.python.org/dev/peps/pep-0592/).
+    #[serde(default)]
+    pub yanked: bool,
+    pub yanked_reason: Option<String>,
+}
+
+/// A release of a package as published on an index.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexRelease {
+    /// Version as published, which may not be a valid PEP 440 version.
+    pub version: String,
+    pub files: Vec<ReleaseFile>,
+}
+
+/// Error fetching metadata from a [PackageIndex].
+#[derive(Error, Debug)]
+pub enum IndexError {
+    /// The metadata was never fetched and the index is [offline](PypiJsonIndex::with_offline),
+    /// or a local index doesn't have it.
+    /// `version` is `None` for the releases of the package.
+    #[error("{} is not available offline", describe(.package, .version))]
+    MissingMetadata {
+        package: String,
+        version: Option<PEP440Version>,
+    },
+    /// The index doesn't know the package, or this version of it.
+    #[error("{} was not found on the index", describe(.package, .version))]
+    NotFound {
+        package: String,
+        version: Option<PEP440Version>,
+    },
+    /// A request failed in a way that may go away, like a timeout or a server error,
+    /// and kept failing for all [retries](Retries).
+    #[error("requesting {url} failed{}: {source}", describe_status(.status))]
+    Transient {
+        url: String,
+        status: Option<StatusCode>,
+        source: reqwest::Error,
+    },
+    /// A request failed in a way that retrying won't fix, like a missing authorization.
+    #[error("requesting {url} failed{}: {source}", describe_status(.status))]
+    Http {
+        url: String,
+        status: Option<StatusCode>,
+        source: reqwest::Error,
+    },
+    /// The index serves no separate metadata of any file of the release, which the Simple API
+    /// only does with [PEP 658](https://www.python.org/dev/peps/pep-0658/).
+    /// The resolver skips such releases.
+    #[error("the index serves no metadata file of {package} {version}")]
+    NoMetadataFile {
+        package: String,
+        version: PEP440Version,
+    },
+    /// The index answered with something that is not a valid document.
+    #[error("malformed response from {url}: {source}")]
+    Malformed {
+        url: String,
+        source: serde_json::Error,
+    },
+}
+
+fn describe(package: &str, version: &Option<PEP440Version>) -> String {
+    match version {
+        Some(version) => format!("metadata of {} {}", package, version),
+        None => format!("list of releases of {}", package),
+    }
+}
+
+fn describe_status(status: &Option<StatusCode>) -> String {
+    match status {
+        Some(status) => format!(" with status {}", status),
+        None => String::new(),
+    }
+}
+
+impl IndexError {
+    fn missing(package: &str, version: Option<&PEP440Version>) -> Self {
+        IndexError::MissingMetadata {
+            package: package.to_string(),
+            version: version.cloned(),
+        }
+    }
+
+    /// Turn a failed request for `url`, the document of `package` or `version` of it, into an error.
+    fn request(
+        url: &str,
+        package: &str,
+        version: Option<&PEP440Version>,
+        source: reqwest::Error,
+    ) -> Self {
+        let status = source.status();
+        if let Some(StatusCode::NOT_FOUND) | Some(StatusCode::GONE) = status {
+            let package = package.to_string();
+            let version = version.cloned();
+            return IndexError::NotFound { package, version };
+        }
+        let url = url.to_string();
+        let transient = match status {
+            Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+            None => source.is_timeout() || source.is_connect() || source.is_body(),
+        };
+        if transient {
+            IndexError::Transient {
+                url,
+                status,
+                source,
+            }
+        } else {
+            IndexError::Http {
+                url,
+                status,
+                source,
+            }
+        }
+    }
+
+    fn malformed(url: &str, source: serde_json::Error) -> Self {
+        IndexError::Malformed {
+            url: url.to_string(),
+            source,
+        }
+    }
+}
+
+/// How often and how patiently requests failing with [IndexError::Transient] are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retries {
+    /// Attempts after the first one.
+    pub max_retries: u32,
+    /// Wait before the first retry, doubling for each further one.
+    pub backoff: Duration,
+    /// Longest wait before a retry.
+    pub max_backoff: Duration,
+}
+
+impl Default for Retries {
+    fn default() -> Self {
+        Retries {
+            max_retries: 3,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Retries {
+    /// Fail on the first transient failure.
+    pub fn none() -> Self {
+        Retries {
+            max_retries: 0,
+            ..Retries::default()
+        }
+    }
+
+    /// Wait before retry number `retry`, counting from 0.
+    fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry);
+        self.backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+    }
+}
+
+/// A successful response, read completely.
+pub(crate) struct Fetched {
+    /// URL the response came from, after redirects.
+    pub url: String,
+    not_modified: bool,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl Fetched {
+    /// The body as a JSON document.
+    fn json<D: DeserializeOwned>(&self) -> Result<D, Box<dyn Error>> {
+        serde_json::from_slice(&self.body).map_err(|e| IndexError::malformed(&self.url, e).into())
+    }
+}
+
+/// How an index is reached, the same for all kinds of indexes:
+/// with a client retrying transient failures, possibly a cache and possibly offline.
+pub(crate) struct Remote {
+    client: reqwest::blocking::Client,
+    /// URL of the index, which its cache entries are kept under.
+    pub url: String,
+    pub cache: Option<MetadataCache>,
+    offline: bool,
+    retries: Retries,
+}
+
+impl Remote {
+    pub fn new(url: &str) -> Self {
+        Remote {
+            client: reqwest::blocking::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            cache: None,
+            offline: false,
+            retries: Retries::default(),
+        }
+    }
+
+    pub fn with_cache(self, cache: MetadataCache) -> Self {
+        Remote {
+            cache: Some(cache),
+            ..self
+        }
+    }
+
+    pub fn with_offline(self, offline: bool) -> Self {
+        Remote { offline, ..self }
+    }
+
+    pub fn with_retries(self, retries: Retries) -> Self {
+        Remote { retries, ..self }
+    }
+
+    /// Request `url`, conditional on `cached` having changed.
+    fn get<T>(&self, url: &str, cached: Option<&Entry<T>>) -> reqwest::Result<Fetched> {
+        let mut request = self.client.get(url);
+        if let Some(entry) = cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = request.send()?.error_for_status()?;
+        let header = |name: HeaderName| {
+            let value = response.headers().get(name)?.to_str().ok()?;
+            Some(value.to_string())
+        };
+        Ok(Fetched {
+            url: response.url().to_string(),
+            not_modified: response.status() == StatusCode::NOT_MODIFIED,
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            body: response.bytes()?.to_vec(),
+        })
+    }
+
+    /// Like [get](Self::get) for `url` with metadata of `package`, or of `version` of it,
+    /// but retrying transient failures.
+    fn get_with_retries<T>(
+        &self,
+        url: &str,
+        package: &str,
+        version: Option<&PEP440Version>,
+        cached: Option<&Entry<T>>,
+    ) -> Result<Fetched, Box<dyn Error>> {
+        let mut retry = 0;
+        loop {
+            let source = match self.get(url, cached) {
+                Ok(fetched) => return Ok(fetched),
+                Err(source) => source,
+            };
+            let error = IndexError::request(url, package, version, source);
+            if !matches!(error, IndexError::Transient { .. }) || retry >= self.retries.max_retries {
+                return Err(error.into());
+            }
+            std::thread::sleep(self.retries.delay(retry));
+            retry += 1;
+        }
+    }
+
+    /// What `parse` makes of `url` with metadata of `package`, or of `version` of it,
+    /// kept in `slot` of the cache if there is one.
+    pub fn fetch<T: Serialize + DeserializeOwned>(
+        &self,
+        url: &str,
+        package: &str,
+        version: Option<&PEP440Version>,
+        slot: Option<EntrySlot<T>>,
+        parse: impl FnOnce(&Fetched) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let slot = match slot {
+            Some(slot) => slot,
+            None if self.offline => return Err(IndexError::missing(package, version).into()),
+            None => return parse(&self.get_with_retries::<T>(url, package, version, None)?),
+        };
+        let cached = match slot.load() {
+            Some(entry) if self.offline || slot.is_fresh(&entry) => return Ok(entry.data),
+            None if self.offline => return Err(IndexError::missing(package, version).into()),
+            cached => cached,
+        };
+        let fetched = self.get_with_retries(url, package, version, cached.as_ref())?;
+        // failing to write the cache only costs requests in later resolutions
+        if let (true, Some(entry)) = (fetched.not_modified, cached) {
+            let entry = entry.revalidated();
+            let _ = slot.store(&entry);
+            return Ok(entry.data);
+        }
+        let entry = Entry::fetched(parse(&fetched)?, fetched.etag, fetched.last_modified);
+        let _ = slot.store(&entry);
+        Ok(entry.data)
+    }
+}
+
+/// Where the metadata of packages comes from, like PyPI, a mirror or a local directory.
+pub trait PackageIndex {
+    /// All releases of `package`, in any order.
+    fn releases(&self, package: &str) -> Result<Vec<IndexRelease>, Box<dyn Error>>;
+
+    /// The `Requires-Dist` entries of `package` at `version`.
+    fn requires_dist(
+        &self,
+        package: &str,
+        version: &PEP440Version,
+    ) -> Result<Vec<String>, Box<dyn Error>>;
+}
+
+impl<I: PackageIndex + ?Sized> PackageIndex for Box<I> {
+    fn releases(&self, package: &str) -> Result<Vec<IndexRelease>, Box<dyn Error>> {
+        (**self).releases(package)
+    }
+
+    fn requires_dist(
+        &self,
+        package: &str,
+        version: &PEP440Version,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        (**self).requires_dist(package, version)
+    }
+}
+
+/// The JSON API of PyPI, or of a mirror serving it, like `https://pypi.org/pypi/requests/json`.
+///
+/// A `file://` URL reads the documents from a local directory laid out like the API,
+/// such as `/srv/mirror/requests/json` and `/srv/mirror/requests/2.25.1/json`.
+pub struct PypiJsonIndex {
+    remote: Remote,
+    /// Documents of the projects whose releases were listed, by normalized name.
+    projects: RefCell<HashMap<String, PypiPackage>>,
+}
+
+impl Default for PypiJsonIndex {
+    fn default() -> Self {
+        PypiJsonIndex::new("https://pypi.org/pypi")
+    }
+}
+
+impl PypiJsonIndex {
+    /// The JSON API below `url`, which is `https://pypi.org/pypi` for PyPI itself.
+    pub fn new(url: &str) -> Self {
+        PypiJsonIndex {
+            remote: Remote::new(url),
+            projects: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Keep what was fetched in `cache` and only ask the index again when it expired.
+    pub fn with_cache(self, cache: MetadataCache) -> Self {
+        PypiJsonIndex {
+            remote: self.remote.with_cache(cache),
+            ..self
+        }
+    }
+
+    /// Never ask the index and serve everything from the cache, however old.
+    /// Metadata that was never cached fails with [IndexError::MissingMetadata].
+    pub fn with_offline(self, offline: bool) -> Self {
+        PypiJsonIndex {
+            remote: self.remote.with_offline(offline),
+            ..self
+        }
+    }
+
+    /// Retry requests failing with [IndexError::Transient] according to `retries`.
+    pub fn with_retries(self, retries: Retries) -> Self {
+        PypiJsonIndex {
+            remote: self.remote.with_retries(retries),
+            ..self
+        }
+    }
+
+    /// The version string `package` published `version` as, which the document of the version
+    /// is found by. It is only known once the releases were listed, the canonical form is used before.
+    fn published_version(&self, package: &str, version: &PEP440Version) -> String {
+        let projects = self.projects.borrow();
+        let releases = projects.get(&normalize_name(package)).map(|p| &p.releases);
+        releases
+            .and_then(|releases| {
+                releases
+                    .keys()
+                    .find(|published| published.parse().ok().as_ref() == Some(version))
+            })
+            .cloned()
+            .unwrap_or_else(|| version.to_string())
+    }
+
+    /// The part of the document of `package`, or of `version` of it, that `extract` picks,
+    /// kept in `slot` of the cache if there is one.
+    /// A version comes along with the string the index publishes it as.
+    fn fetch<T: Serialize + DeserializeOwned>(
+        &self,
+        package: &str,
+        version: Option<(&PEP440Version, &str)>,
+        slot: Option<EntrySlot<T>>,
+        extract: impl FnOnce(PypiPackage) -> T,
+    ) -> Result<T, Box<dyn Error>> {
+        let path = match version {
+            Some((_, published)) => format!("{}/{}/json", package, published),
+            None => format!("{}/json", package),
+        };
+        let version = version.map(|(version, _)| version);
+        if let Some(dir) = self.remote.url.strip_prefix("file://") {
+            let path = Path::new(dir).join(path);
+            let content = match std::fs::read(&path) {
+                Ok(content) => content,
+                Err(e) if e.kind() == ErrorKind::NotFound && self.remote.offline => {
+                    return Err(IndexError::missing(package, version).into())
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    return Err(IndexError::NotFound {
+                        package: package.to_string(),
+                        version: version.cloned(),
+                    }
+                    .into())
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let document = serde_json::from_slice(&content)
+                .map_err(|e| IndexError::malformed(&path.display().to_string(), e))?;
+            return Ok(extract(document));
+        }
+        let url = format!("{}/{}", self.remote.url, path);
+        self.remote.fetch(&url, package, version, slot, |fetched| {
+            Ok(extract(fetched.json()?))
+        })
+    }
+}
+
+/// The parts of a JSON document of the index that are used.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PypiPackage {
+    info: PackageInfo,
+    #[serde(default)]
+    releases: HashMap<String, Vec<ReleaseFile>>,
+}
+
+/// Metadata of a release, the latest one in the document of the whole project.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PackageInfo {
+    #[serde(default)]
+    version: Option<String>,
+    requires_dist: Option<Vec<String>>,
+}
+
+impl PackageIndex for PypiJsonIndex {
+    fn releases(&self, package: &str) -> Result<Vec<IndexRelease>, Box<dyn Error>> {
+        let slot = self
+            .remote
+            .cache
+            .as_ref()
+            .map(|cache| cache.project(&self.remote.url, package));
+        let project: PypiPackage = self.fetch(package, None, slot, |data| data)?;
+        let releases = project
+            .releases
+            .iter()
+            .map(|(version, files)| IndexRelease {
+                version: version.clone(),
+                files: files.clone(),
+            })
+            .collect();
+        self.projects
+            .borrow_mut()
+            .insert(normalize_name(package), project);
+        Ok(releases)
+    }
+
+    fn requires_dist(
+        &self,
+        package: &str,
+        version: &PEP440Version,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        // the document of the project already has the metadata of the latest release
+        if let Some(project) = self.projects.borrow().get(&normalize_name(package)) {
+            let latest = project.info.version.as_ref().and_then(|v| v.parse().ok());
+            if latest.as_ref() == Some(version) {
+                return Ok(project.info.requires_dist.clone().unwrap_or_default());
+            }
+        }
+        let published = self.published_version(package, version);
+        let slot = self
+            .remote
+            .cache
+            .as_ref()
+            .map(|cache| cache.requires_dist(&self.remote.url, package, &published));
+        self.fetch(package, Some((version, &published)), slot, |data| {
+            data.info.requires_dist.unwrap_or_default()
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::cache::MetadataCache;
+    use crate::index::{
+        IndexError, IndexRelease, PackageIndex, PypiJsonIndex, ReleaseFile, Retries,
+    };
+    use crate::version::PEP440Version;
+    use reqwest::StatusCode;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Serve HTTP on localhost with `respond` building the response to each request.
+    /// Returns the URL of the server and the requests it received so far.
+    pub(crate) fn serve(
+        respond: impl Fn(&str) -> String + Send + 'static,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = String::new();
+                let mut reader = BufReader::new(&stream);
+                while reader.read_line(&mut request).unwrap() > 2 {}
+                let response = respond(&request.to_lowercase());
+                received.lock().unwrap().push(request);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    pub(crate) fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nconnection: close\r\ncontent-length: {}\r\n{}\r\n{}",
+            status,
+            body.len(),
+            headers,
+            body
+        )
+    }
+
+    #[test]
+    fn test_cache_revalidates_with_etag() {
+        let (url, requests) = serve(|request| {
+            if request.contains("if-none-match: \"1\"") {
+                return response("304 Not Modified", "etag: \"1\"\r\n", "");
+            }
+            let body = r#"{
+                "info": {"requires_dist": ["bar (>=1)"]},
+                "releases": {"1.0": [{"requires_python": ">=3.6"}]}
+            }"#;
+            response("200 OK", "etag: \"1\"\r\n", body)
+        });
+        let dir =
+            std::env::temp_dir().join(format!("poetry-resolver-revalidate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let expired = MetadataCache::new(&dir).with_releases_ttl(Duration::from_secs(0));
+        let index = PypiJsonIndex::new(&url).with_cache(expired.clone());
+        let releases = vec![IndexRelease {
+            version: "1.0".into(),
+            files: vec![ReleaseFile {
+                requires_python: Some(">=3.6".into()),
+                ..ReleaseFile::default()
+            }],
+        }];
+        assert_eq!(index.releases("foo").unwrap(), releases);
+        assert_eq!(index.releases("foo").unwrap(), releases);
+        {
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests.len(), 2);
+            assert!(requests[0].starts_with("GET /foo/json "));
+            assert!(!requests[0].to_lowercase().contains("if-none-match"));
+            assert!(requests[1].to_lowercase().contains("if-none-match: \"1\""));
+        }
+
+        let index = PypiJsonIndex::new(&url).with_cache(MetadataCache::new(&dir));
+        assert_eq!(index.releases("Foo").unwrap(), releases);
+        let version = PEP440Version::new(1, 0, 0);
+        assert_eq!(
+            index.requires_dist("foo", &version).unwrap(),
+            vec!["bar (>=1)"]
+        );
+        assert_eq!(
+            index.requires_dist("foo", &version).unwrap(),
+            vec!["bar (>=1)"]
+        );
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        // the document is found by the version string the index published
+        assert!(requests.lock().unwrap()[2].starts_with("GET /foo/1.0/json "));
+
+        let offline = PypiJsonIndex::new(&url)
+            .with_cache(expired)
+            .with_offline(true);
+        assert_eq!(offline.releases("foo").unwrap(), releases);
+        assert_eq!(
+            offline.requires_dist("foo", &version).unwrap(),
+            vec!["bar (>=1)"]
+        );
+        let error = offline
+            .requires_dist("foo", &PEP440Version::new(2, 0, 0))
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(IndexError::MissingMetadata {
+                version: Some(_),
+                ..
+            })
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_local_directory() {
+        let dir =
+            std::env::temp_dir().join(format!("poetry-resolver-local-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("foo").join("1.0")).unwrap();
+        let project =
+            r#"{"info": {"version": "2.0", "requires_dist": []}, "releases": {"1.0": []}}"#;
+        std::fs::write(dir.join("foo").join("json"), project).unwrap();
+        let release = r#"{"info": {"version": "1.0", "requires_dist": ["bar"]}}"#;
+        std::fs::write(dir.join("foo").join("1.0").join("json"), release).unwrap();
+
+        let index = PypiJsonIndex::new(&format!("file://{}", dir.display()));
+        assert_eq!(index.releases("foo").unwrap().len(), 1);
+        let version = "1.0".parse().unwrap();
+        assert_eq!(index.requires_dist("foo", &version).unwrap(), vec!["bar"]);
+        let error = index.releases("bar").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(IndexError::NotFound { version: None, .. })
+        ));
+        let offline = PypiJsonIndex::new(&format!("file://{}", dir.display())).with_offline(true);
+        let error = offline.releases("bar").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "list of releases of bar is not available offline"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_latest_requires_dist_from_project() {
+        let (url, requests) = serve(|request| {
+            let body = if request.starts_with("get /foo/json ") {
+                r#"{
+                    "info": {"version": "2.0", "requires_dist": ["bar"]},
+                    "releases": {"1.0": [], "1.0post1": [], "2.0": []}
+                }"#
+            } else {
+                r#"{"info": {"version": "1.0", "requires_dist": null}}"#
+            };
+            response("200 OK", "", body)
+        });
+        let index = PypiJsonIndex::new(&url);
+        assert_eq!(index.releases("foo").unwrap().len(), 3);
+        let latest = PEP440Version::new(2, 0, 0);
+        assert_eq!(index.requires_dist("foo", &latest).unwrap(), vec!["bar"]);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        let older = PEP440Version::new(1, 0, 0);
+        assert!(index.requires_dist("foo", &older).unwrap().is_empty());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        let post: PEP440Version = "1.0.post1".parse().unwrap();
+        assert!(index.requires_dist("foo", &post).unwrap().is_empty());
+        assert!(requests.lock().unwrap()[2].starts_with("GET /foo/1.0post1/json "));
+    }
+
+    #[test]
+    fn test_request_errors() {
+        let flaky = Arc::new(AtomicUsize::new(0));
+        let attempts = flaky.clone();
+        let (url, requests) = serve(move |request| {
+            let path = request.split(' ').nth(1).unwrap_or_default();
+            match path {
+                "/flaky/json" if attempts.fetch_add(1, Ordering::SeqCst) < 2 => {
+                    response("503 Service Unavailable", "", "")
+                }
+                "/flaky/json" => response("200 OK", "", r#"{"info": {}, "releases": {}}"#),
+                "/down/json" => response("503 Service Unavailable", "", ""),
+                "/private/json" => response("403 Forbidden", "", ""),
+                "/broken/json" => response("200 OK", "", "<html>"),
+                _ => response("404 Not Found", "", ""),
+            }
+        });
+        let retries = Retries {
+            max_retries: 2,
+            backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        };
+        let index = PypiJsonIndex::new(&url).with_retries(retries);
+        assert!(index.releases("flaky").unwrap().is_empty());
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        let error = |package: &str| {
+            let error = index.releases(package).unwrap_err();
+            *error.downcast::<IndexError>().unwrap()
+        };
+        assert!(matches!(
+            error("down"),
+            IndexError::Transient {
+                status: Some(StatusCode::SERVICE_UNAVAILABLE),
+                ..
+            }
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 6);
+        assert!(matches!(
+            error("private"),
+            IndexError::Http {
+                status: Some(StatusCode::FORBIDDEN),
+                ..
+            }
+        ));
+        assert!(matches!(error("broken"), IndexError::Malformed { .. }));
+        assert!(matches!(
+            error("missing"),
+            IndexError::NotFound { version: None, .. }
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 9);
+        let version = PEP440Version::new(1, 0, 0);
+        let error = index.requires_dist("missing", &version).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "metadata of missing 1.0.0 was not found on the index"
+        );
+
+        let index = PypiJsonIndex::new(&url).with_retries(Retries::none());
+        assert!(index.releases("down").is_err());
+        assert_eq!(requests.lock().unwrap().len(), 11);
+    }
+
+    #[test]
+    fn test_retry_delays() {
+        let retries = Retries::default();
+        let delays: Vec<_> = (0..7)
+            .map(|retry| retries.delay(retry).as_millis())
+            .collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 4000, 8000, 10000, 10000]);
+        assert_eq!(retries.delay(u32::MAX), retries.max_backoff);
+    }
+}
@@ -1,72 +1,275 @@
+mod cache;
+mod index;
+mod markers;
 mod poetry_provider;
 mod provider;
 mod ranges;
+mod report;
+mod requirement;
+mod simple_index;
+mod solution;
 mod version;
 
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyString};
 use pyo3::wrap_pyfunction;
 
 
+pub use crate::cache::MetadataCache;
+pub use crate::index::{IndexError, IndexRelease, PackageIndex, PypiJsonIndex, ReleaseFile, Retries};
+pub use crate::markers::Environment;
+pub use crate::ranges::{PrereleasePolicy, RequirementParseError};
+pub use crate::simple_index::SimpleIndex;
+pub use crate::solution::{Category, ResolvedPackage};
+pub use crate::version::{PEP440Version, VersionParseError};
+
 use crate::poetry_provider::{PoetryProvider, RootPackage};
-use crate::ranges::parse_dependency;
-
-pub fn resolve(root: &str, version: &str, requires: Vec<(&str, &str)>, dev_requires: Vec<(&str, &str)>) -> Vec<(String, String)> {
-    let version = version.parse().unwrap();
-    let dependencies = requires
-        .iter()
-        .chain(dev_requires.iter())
-        .map(|(name, range)| {
-            let range = parse_dependency(&format!("{} ({})", name, range)).unwrap();
-            range
-        })
-        .collect();
+use crate::provider::PypiProvider;
+use crate::ranges::{names_prerelease, parse_poetry_constraint, parse_specifier};
+use crate::requirement::Requirement;
+use crate::report::PoetryReporter;
+use crate::solution::collect_solution;
+use pubgrub::error::PubGrubError;
+use pubgrub::range::Range;
+use pubgrub::report::DerivationTree;
+use pubgrub::solver::DependencyConstraints;
+use std::collections::HashMap;
+use thiserror::Error;
+
+create_exception!(_poetry_ext, SolverProblemError, PyException);
+create_exception!(_poetry_ext, ProviderError, PyException);
+create_exception!(_poetry_ext, NetworkError, ProviderError);
+create_exception!(_poetry_ext, MissingMetadataError, ProviderError);
+
+/// Error resolving the dependencies of a project.
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("invalid version of the root package: {0}")]
+    InvalidVersion(#[from] VersionParseError),
+    #[error(transparent)]
+    InvalidRequirement(#[from] RequirementParseError),
+    /// There is no set of versions satisfying all requirements.
+    #[error("{}", PoetryReporter::report_with_prereleases(.tree, .prereleases))]
+    NoSolution {
+        tree: Box<DerivationTree<String, PEP440Version>>,
+        /// Pre-releases the resolution was allowed to pick, which decides on the hints.
+        prereleases: PrereleasePolicy,
+    },
+    /// The index failed to provide the metadata of a package, see [IndexError].
+    #[error("{context}: {source}")]
+    Index {
+        context: String,
+        source: Box<IndexError>,
+    },
+    /// Package metadata could not be fetched.
+    #[error("{context}: {source}")]
+    Network {
+        context: String,
+        source: reqwest::Error,
+    },
+    /// The dependency provider failed for another reason.
+    #[error("{context}: {source}")]
+    Provider {
+        context: String,
+        source: Box<dyn std::error::Error>,
+    },
+}
+
+impl ResolveError {
+    fn provider(context: String, source: Box<dyn std::error::Error>) -> Self {
+        let source = match source.downcast::<IndexError>() {
+            Ok(source) => return ResolveError::Index { context, source },
+            Err(source) => source,
+        };
+        match source.downcast::<reqwest::Error>() {
+            Ok(source) => ResolveError::Network {
+                context,
+                source: *source,
+            },
+            Err(source) => ResolveError::Provider { context, source },
+        }
+    }
+}
+
+impl From<PubGrubError<String, PEP440Version>> for ResolveError {
+    fn from(error: PubGrubError<String, PEP440Version>) -> Self {
+        match error {
+            PubGrubError::NoSolution(tree) => ResolveError::NoSolution {
+                tree: Box::new(tree),
+                prereleases: PrereleasePolicy::default(),
+            },
+            PubGrubError::ErrorRetrievingDependencies {
+                package,
+                version,
+                source,
+            } => ResolveError::provider(
+                format!("retrieving dependencies of {} {} failed", package, version),
+                source,
+            ),
+            PubGrubError::ErrorChoosingPackageVersion(source) => {
+                ResolveError::provider("choosing a package version failed".into(), source)
+            }
+            PubGrubError::ErrorInShouldCancel(source) => {
+                ResolveError::provider("resolution was cancelled".into(), source)
+            }
+            error => ResolveError::provider("resolution failed".into(), error.to_string().into()),
+        }
+    }
+}
+
+impl From<ResolveError> for PyErr {
+    fn from(error: ResolveError) -> Self {
+        let message = error.to_string();
+        match error {
+            ResolveError::InvalidVersion(_) | ResolveError::InvalidRequirement(_) => {
+                PyValueError::new_err(message)
+            }
+            ResolveError::NoSolution { .. } => SolverProblemError::new_err(message),
+            ResolveError::Index { source, .. } => match *source {
+                IndexError::MissingMetadata { .. } => MissingMetadataError::new_err(message),
+                IndexError::Transient { .. } | IndexError::Http { .. } => NetworkError::new_err(message),
+                IndexError::NotFound { .. } | IndexError::NoMetadataFile { .. } | IndexError::Malformed { .. } => ProviderError::new_err(message),
+            },
+            ResolveError::Network { .. } => NetworkError::new_err(message),
+            ResolveError::Provider { .. } => ProviderError::new_err(message),
+        }
+    }
+}
+
+/// Resolve the dependencies of the `root` project and return all packages of the solution.
+///
+/// `requires` and `dev_requires` are pairs of a requirement name like `requests[security]`
+/// and a constraint as Poetry stores it, like `^2.18` or `>=2.18,<3`.
+/// Like in `pyproject.toml`, a `python` requirement gives the python versions the project supports,
+/// and only releases supporting all of them are picked.
+/// Without it, releases must support the python version of `environment`.
+/// Environment markers are always evaluated against `environment`, even with a `python` requirement:
+/// for `python = "^3.8"` and a python 3.9 environment, a requirement marked `python_version < "3.9"` is left out.
+/// Pre-releases are picked according to `prereleases`, and of packages whose requirements name one.
+/// Yanked releases are only picked when pinned, with a warning on the resolved package.
+/// Package metadata comes from `index`, like a [PypiJsonIndex] with a cache or offline.
+pub fn resolve(root: &str, version: &str, requires: Vec<(&str, &str)>, dev_requires: Vec<(&str, &str)>, environment: &Environment, prereleases: &PrereleasePolicy, index: impl PackageIndex) -> Result<Vec<ResolvedPackage>, ResolveError> {
+    let version = version.parse()?;
+    let (python, requires): (Vec<_>, Vec<_>) = requires.into_iter().partition(|(name, _)| name.eq_ignore_ascii_case("python"));
+    let python = match python.last() {
+        Some((_, constraint)) => parse_poetry_constraint(constraint)?,
+        None => parse_specifier(&format!("=={}", environment.python_full_version))?,
+    };
+    let mut prereleases = prereleases.clone();
+    let mut parse = |requires: Vec<(&str, &str)>| -> Result<Vec<(String, Range<PEP440Version>)>, RequirementParseError> {
+        let mut dependencies = Vec::new();
+        for (name, constraint) in requires {
+            let requirement: Requirement = name.parse()?;
+            if !requirement.applies_to(environment, &[]) {
+                continue;
+            }
+            let range = parse_poetry_constraint(constraint)?.intersection(&requirement.range());
+            if names_prerelease(constraint) || requirement.names_prerelease() {
+                prereleases.packages.push(requirement.name.clone());
+            }
+            dependencies.extend(requirement.dependencies()?.into_iter().map(|(package, _)| (package, range.clone())));
+        }
+        Ok(dependencies)
+    };
+    let requires = parse(requires)?;
+    let main: Vec<String> = requires.iter().map(|(name, _)| name.clone()).collect();
+    let mut dependencies = DependencyConstraints::default();
+    for (name, range) in requires.into_iter().chain(parse(dev_requires)?) {
+        let range = match dependencies.get(&name) {
+            Some(existing) => range.intersection(existing),
+            None => range,
+        };
+        dependencies.insert(name, range);
+    }
     let root = RootPackage {
         package: String::from(root),
         version,
         dependencies,
     };
-    let provider = PoetryProvider::new(root.clone());
-    let solution = pubgrub::solver::resolve(&provider, root.package.clone(), root.version.clone()).unwrap();
-    solution.iter().filter_map(|(p, v)| {
-        if p == &root.package {
-            Some((p.clone(), format!("{}", v)))
-        }
-        else {
-            None
+    let provider = PoetryProvider::from_remote(root.clone(), PypiProvider::new(index))
+        .with_environment(environment.clone())
+        .with_prereleases(prereleases.clone())
+        .with_python(python);
+    let solution = pubgrub::solver::resolve(&provider, root.package.clone(), root.version.clone()).map_err(|error| match error {
+        PubGrubError::NoSolution(tree) => ResolveError::NoSolution { tree: Box::new(tree), prereleases },
+        error => error.into(),
+    })?;
+    let mut packages = collect_solution(&provider, &root.package, &solution, &main)
+        .map_err(|e| ResolveError::provider("collecting the solution failed".into(), e))?;
+    for package in &mut packages {
+        if let Some(reason) = provider.yanked_reason(&package.name, &package.version) {
+            let mut warning = format!("{} {} is a yanked version", package.name, package.version);
+            if !reason.is_empty() {
+                warning.push_str(&format!(", reason for being yanked: {}", reason));
+            }
+            package.warnings.push(warning);
         }
-    }).collect()
+    }
+    Ok(packages)
 }
 
-#[pyfunction]
+/// Metadata is kept in the user's cache directory. With `offline`, it is only taken from there.
+/// Packages come from PyPI, or from the Simple API at `index_url` if given.
+/// `environment` replaces marker variables of the default [Environment],
+/// so passing `packaging.markers.default_environment()` resolves for the running interpreter.
+/// `allow_prereleases` is `True` to allow pre-releases of every package, or a list of packages to allow them for.
+#[pyfunction(offline = "false", index_url = "None", environment = "None", allow_prereleases = "None")]
+#[allow(clippy::too_many_arguments)] // keyword arguments on the Python side
 fn resolve_pywrapper(
     root: &str,
     version: &str,
     requires: Vec<(&str, &str)>,
     dev_requires: Vec<(&str, &str)>,
-) -> PyResult<Vec<(String, String)>> {
-    // not an impl yet, just playing with stuff
-    println!("rust side");
-    println!("root: {}, version: {}", root, version);
-    println!("requires: {:?}", requires);
-    println!("dev_requires: {:?}", dev_requires);
-
-    let solution = resolve(root, version, requires, dev_requires);
-    println!("solution: {:?}", solution);
-    Ok(solution)
+    offline: bool,
+    index_url: Option<&str>,
+    environment: Option<HashMap<&str, &str>>,
+    allow_prereleases: Option<&PyAny>,
+) -> PyResult<Vec<ResolvedPackage>> {
+    let environment = Environment::from_markers(environment.unwrap_or_default())
+        .map_err(|name| PyValueError::new_err(format!("unknown marker variable '{}'", name)))?;
+    let prereleases = match allow_prereleases.map(|allow| (allow, allow.downcast::<PyBool>())) {
+        None => PrereleasePolicy::default(),
+        Some((_, Ok(all))) if all.is_true() => PrereleasePolicy::allow_all(),
+        Some((_, Ok(_))) => PrereleasePolicy::default(),
+        // a string is a sequence too, but not one of package names
+        Some((allow, Err(_))) if allow.downcast::<PyString>().is_ok() => {
+            return Err(PyValueError::new_err("allow_prereleases must be a bool or a list of package names"))
+        }
+        Some((allow, Err(_))) => PrereleasePolicy { packages: allow.extract()?, ..PrereleasePolicy::default() },
+    };
+    let cache = MetadataCache::user_default();
+    let index: Box<dyn PackageIndex> = match (index_url, cache) {
+        (Some(url), Some(cache)) => Box::new(SimpleIndex::new(url).with_offline(offline).with_cache(cache)),
+        (Some(url), None) => Box::new(SimpleIndex::new(url).with_offline(offline)),
+        (None, Some(cache)) => Box::new(PypiJsonIndex::default().with_offline(offline).with_cache(cache)),
+        (None, None) => Box::new(PypiJsonIndex::default().with_offline(offline)),
+    };
+    Ok(resolve(root, version, requires, dev_requires, &environment, &prereleases, index)?)
 }
 
-//fn from_constraint(constraint: &str) -> Range
-
 /// A Python module implemented in Rust.
 #[pymodule]
-fn _poetry_ext(_py: Python, m: &PyModule) -> PyResult<()> {
+fn _poetry_ext(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(resolve_pywrapper, m)?)?;
+    m.add("SolverProblemError", py.get_type::<SolverProblemError>())?;
+    m.add("ProviderError", py.get_type::<ProviderError>())?;
+    m.add("NetworkError", py.get_type::<NetworkError>())?;
+    m.add("MissingMetadataError", py.get_type::<MissingMetadataError>())?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::resolve;
+    use crate::index::{IndexError, PypiJsonIndex};
+    use crate::markers::Environment;
+    use crate::ranges::PrereleasePolicy;
+    use crate::{resolve, ResolveError};
+    use crate::version::PEP440Version;
+    use pubgrub::range::Range;
+    use pubgrub::solver::OfflineDependencyProvider;
+    use crate::solution::Category;
 
 
     #[test]
@@ -87,8 +290,7 @@ mod tests {
             ("packaging", ">=20.4,<21"),
             ("virtualenv", ">20.0.26,<21"),
             ("keyring", ">=21.2.0,<22"),
-            // missing extra "filecache"
-            ("cachecontrol", ">=0.12.4,<0.13"),
+            ("cachecontrol[filecache]", ">=0.12.4,<0.13"),
         ], vec![
             ("pytest", ">=5.4.3,<6"),
             ("pre-commit", ">=2.6,<3"),
@@ -99,6 +301,78 @@ mod tests {
             ("httpretty", ">=1.0,<2"),
             ("urllib3", "==1.25.10"),
             ("setuptools-rust", ">=0.11.5,<0.12")
-        ]);
+        ], &Environment::default(), &PrereleasePolicy::default(), PypiJsonIndex::default()).unwrap();
+        assert!(!solution.is_empty());
+        assert!(solution.iter().all(|p| p.name != "poetry"));
+        let requests = solution.iter().find(|p| p.name == "requests").unwrap();
+        assert!(requests.required_by.contains(&"poetry".to_string()));
+        let cachecontrol = solution.iter().find(|p| p.name == "cachecontrol").unwrap();
+        assert_eq!(cachecontrol.extras, vec!["filecache"]);
+        let pytest = solution.iter().find(|p| p.name == "pytest").unwrap();
+        assert_eq!(pytest.category, Category::Dev);
+    }
+
+    #[test]
+    fn test_no_solution_explains_the_conflict() {
+        let mut provider = OfflineDependencyProvider::<String, PEP440Version>::new();
+        let one = PEP440Version::one();
+        let range = |spec: &str| crate::ranges::parse_specifier(spec).unwrap();
+        provider.add_dependencies("root".into(), one.clone(), vec![("a".into(), Range::any()), ("b".into(), Range::any())]);
+        provider.add_dependencies("a".into(), one.clone(), vec![("c".into(), range("<2"))]);
+        provider.add_dependencies("b".into(), one.clone(), vec![("c".into(), range(">=2"))]);
+        provider.add_dependencies("c".into(), one.clone(), vec![]);
+        provider.add_dependencies("c".into(), PEP440Version::new(2, 0, 0), vec![]);
+        let error: ResolveError = pubgrub::solver::resolve(&provider, "root".into(), one)
+            .unwrap_err()
+            .into();
+        assert!(matches!(error, ResolveError::NoSolution { .. }));
+        let message = error.to_string();
+        assert!(message.contains("b depends on c (>=2)"), "{}", message);
+        assert!(message.contains("a (1.0.0) depends on c (<2)"), "{}", message);
+        assert!(message.ends_with("version solving failed."), "{}", message);
+    }
+
+    #[test]
+    fn test_invalid_root() {
+        let error = resolve("root", "1.0", vec![("foo", ">=>1")], vec![], &Environment::default(), &PrereleasePolicy::default(), PypiJsonIndex::default()).unwrap_err();
+        assert!(matches!(error, ResolveError::InvalidRequirement(_)));
+        let error = resolve("root", "one", vec![], vec![], &Environment::default(), &PrereleasePolicy::default(), PypiJsonIndex::default()).unwrap_err();
+        assert!(matches!(error, ResolveError::InvalidVersion(_)));
+        let error = resolve("root", "1.0", vec![("python", "^3.6 ||")], vec![], &Environment::default(), &PrereleasePolicy::default(), PypiJsonIndex::default()).unwrap_err();
+        assert!(matches!(error, ResolveError::InvalidRequirement(_)));
+    }
+
+    #[test]
+    fn test_markers_follow_the_environment_not_the_python_requirement() {
+        let requires = || vec![("python", "^3.8"), ("foo ; python_version < '3.9'", "^1.0")];
+        let index = || PypiJsonIndex::default().with_offline(true);
+        let python39 = Environment::default().with_python_version("3.9.0");
+        let solution = resolve("root", "1.0", requires(), vec![], &python39, &PrereleasePolicy::default(), index()).unwrap();
+        assert!(solution.is_empty());
+        // only fails because foo is required and not available offline
+        let python38 = Environment::default().with_python_version("3.8.0");
+        let error = resolve("root", "1.0", requires(), vec![], &python38, &PrereleasePolicy::default(), index()).unwrap_err();
+        assert!(matches!(&error, ResolveError::Index { source, .. } if matches!(source.as_ref(), IndexError::MissingMetadata { package, .. } if package == "foo")), "{}", error);
+    }
+
+    #[test]
+    fn test_offline_without_metadata() {
+        let index = PypiJsonIndex::default().with_offline(true);
+        let error = resolve("root", "1.0", vec![("foo", "^1.0")], vec![], &Environment::default(), &PrereleasePolicy::default(), index).unwrap_err();
+        match &error {
+            ResolveError::Index { source, .. } => assert!(matches!(source.as_ref(), IndexError::MissingMetadata { package, version: None } if package == "foo")),
+            error => panic!("unexpected error: {}", error),
+        }
+        assert!(error.to_string().ends_with("list of releases of foo is not available offline"), "{}", error);
+    }
+
+    #[test]
+    fn test_provider_errors_are_classified() {
+        let source = reqwest::blocking::get("not a url").unwrap_err();
+        let error = ResolveError::provider("fetching foo".into(), source.into());
+        assert!(matches!(error, ResolveError::Network { .. }));
+        let error = ResolveError::provider("fetching foo".into(), "bad metadata".into());
+        assert!(matches!(error, ResolveError::Provider { .. }));
+        assert_eq!(error.to_string(), "fetching foo: bad metadata");
     }
 }
@@ -0,0 +1,691 @@
+use crate::ranges::{Compare, RequirementParseError, RequirementParseReason, VersionSpecifier};
+use crate::requirement::{normalize_name, Cursor};
+use crate::version::PEP440Version;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The interpreter and platform requirements are resolved for,
+/// with the environment marker variables of
+/// [PEP 508](https://www.python.org/dev/peps/pep-0508/#environment-markers).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Environment {
+    pub implementation_name: String,
+    pub implementation_version: String,
+    pub os_name: String,
+    pub platform_machine: String,
+    pub platform_python_implementation: String,
+    pub platform_release: String,
+    pub platform_system: String,
+    pub platform_version: String,
+    pub python_full_version: String,
+    pub python_version: String,
+    pub sys_platform: String,
+}
+
+/// CPython 3.9.0 on x86-64 Linux.
+impl Default for Environment {
+    fn default() -> Self {
+        Environment {
+            implementation_name: "cpython".into(),
+            implementation_version: "3.9.0".into(),
+            os_name: "posix".into(),
+            platform_machine: "x86_64".into(),
+            platform_python_implementation: "CPython".into(),
+            platform_release: "".into(),
+            platform_system: "Linux".into(),
+            platform_version: "".into(),
+            python_full_version: "3.9.0".into(),
+            python_version: "3.9".into(),
+            sys_platform: "linux".into(),
+        }
+    }
+}
+
+impl Environment {
+    /// The same environment running another Python version like `3.7.4`.
+    pub fn with_python_version(self, python_full_version: &str) -> Self {
+        let python_version: Vec<&str> = python_full_version.split('.').take(2).collect();
+        Environment {
+            implementation_version: python_full_version.into(),
+            python_full_version: python_full_version.into(),
+            python_version: python_version.join("."),
+            ..self
+        }
+    }
+
+    /// The default environment with the marker variables in `markers` replaced,
+    /// like the dict `packaging.markers.default_environment()` returns.
+    /// Giving only `python_full_version` also sets the other python versions to match.
+    ///
+    /// Fails with the name of the first variable that is not part of an environment.
+    pub fn from_markers<'a>(
+        markers: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self, String> {
+        let markers: Vec<_> = markers.into_iter().collect();
+        let mut environment = Environment::default();
+        if let Some((_, version)) = markers
+            .iter()
+            .find(|(name, _)| *name == "python_full_version")
+        {
+            environment = environment.with_python_version(version);
+        }
+        for (name, value) in markers {
+            let variable = match name.parse() {
+                Ok(MarkerVariable::Extra) | Err(()) => return Err(name.to_string()),
+                Ok(variable) => variable,
+            };
+            *environment.get_mut(variable) = value.to_string();
+        }
+        Ok(environment)
+    }
+
+    fn get_mut(&mut self, variable: MarkerVariable) -> &mut String {
+        match variable {
+            MarkerVariable::ImplementationName => &mut self.implementation_name,
+            MarkerVariable::ImplementationVersion => &mut self.implementation_version,
+            MarkerVariable::OsName => &mut self.os_name,
+            MarkerVariable::PlatformMachine => &mut self.platform_machine,
+            MarkerVariable::PlatformPythonImplementation => {
+                &mut self.platform_python_implementation
+            }
+            MarkerVariable::PlatformRelease => &mut self.platform_release,
+            MarkerVariable::PlatformSystem => &mut self.platform_system,
+            MarkerVariable::PlatformVersion => &mut self.platform_version,
+            MarkerVariable::PythonFullVersion => &mut self.python_full_version,
+            MarkerVariable::PythonVersion => &mut self.python_version,
+            MarkerVariable::SysPlatform => &mut self.sys_platform,
+            MarkerVariable::Extra => unreachable!("extras are not part of the environment"),
+        }
+    }
+
+    /// Value of a marker variable.
+    /// Extras are not part of the environment, so `extra` is always empty.
+    pub fn get(&self, variable: MarkerVariable) -> &str {
+        match variable {
+            MarkerVariable::ImplementationName => &self.implementation_name,
+            MarkerVariable::ImplementationVersion => &self.implementation_version,
+            MarkerVariable::OsName => &self.os_name,
+            MarkerVariable::PlatformMachine => &self.platform_machine,
+            MarkerVariable::PlatformPythonImplementation => &self.platform_python_implementation,
+            MarkerVariable::PlatformRelease => &self.platform_release,
+            MarkerVariable::PlatformSystem => &self.platform_system,
+            MarkerVariable::PlatformVersion => &self.platform_version,
+            MarkerVariable::PythonFullVersion => &self.python_full_version,
+            MarkerVariable::PythonVersion => &self.python_version,
+            MarkerVariable::SysPlatform => &self.sys_platform,
+            MarkerVariable::Extra => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerVariable {
+    ImplementationName,
+    ImplementationVersion,
+    OsName,
+    PlatformMachine,
+    PlatformPythonImplementation,
+    PlatformRelease,
+    PlatformSystem,
+    PlatformVersion,
+    PythonFullVersion,
+    PythonVersion,
+    SysPlatform,
+    Extra,
+}
+
+impl FromStr for MarkerVariable {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let v = match s {
+            "implementation_name" => Self::ImplementationName,
+            "implementation_version" => Self::ImplementationVersion,
+            "os_name" | "os.name" => Self::OsName,
+            "platform_machine" | "platform.machine" => Self::PlatformMachine,
+            "platform_python_implementation"
+            | "platform.python_implementation"
+            | "python_implementation" => Self::PlatformPythonImplementation,
+            "platform_release" => Self::PlatformRelease,
+            "platform_system" => Self::PlatformSystem,
+            "platform_version" | "platform.version" => Self::PlatformVersion,
+            "python_full_version" => Self::PythonFullVersion,
+            "python_version" => Self::PythonVersion,
+            "sys_platform" | "sys.platform" => Self::SysPlatform,
+            "extra" => Self::Extra,
+            _ => return Err(()),
+        };
+        Ok(v)
+    }
+}
+
+impl Display for MarkerVariable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::ImplementationName => "implementation_name",
+            Self::ImplementationVersion => "implementation_version",
+            Self::OsName => "os_name",
+            Self::PlatformMachine => "platform_machine",
+            Self::PlatformPythonImplementation => "platform_python_implementation",
+            Self::PlatformRelease => "platform_release",
+            Self::PlatformSystem => "platform_system",
+            Self::PlatformVersion => "platform_version",
+            Self::PythonFullVersion => "python_full_version",
+            Self::PythonVersion => "python_version",
+            Self::SysPlatform => "sys_platform",
+            Self::Extra => "extra",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Either side of a marker expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkerValue {
+    Variable(MarkerVariable),
+    Literal(String),
+}
+
+impl Display for MarkerValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Variable(variable) => write!(f, "{}", variable),
+            // there are no escapes, so use the quote that does not occur in the string
+            Self::Literal(value) if value.contains('"') => write!(f, "'{}'", value),
+            Self::Literal(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerOperator {
+    Compare(Compare),
+    In,
+    NotIn,
+}
+
+impl Display for MarkerOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Compare(cmp) => write!(f, "{}", cmp),
+            Self::In => write!(f, "in"),
+            Self::NotIn => write!(f, "not in"),
+        }
+    }
+}
+
+/// A single comparison like `python_version < "3.8"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkerExpression {
+    pub lhs: MarkerValue,
+    pub operator: MarkerOperator,
+    pub rhs: MarkerValue,
+}
+
+impl MarkerExpression {
+    /// Evaluate the expression with `extra` being the (normalized) name of a requested extra.
+    fn evaluate(&self, environment: &Environment, extra: &str) -> bool {
+        let involves_extra = [&self.lhs, &self.rhs]
+            .iter()
+            .any(|v| **v == MarkerValue::Variable(MarkerVariable::Extra));
+        let resolve = |value: &MarkerValue| match value {
+            // extra names are compared in their normalized form
+            MarkerValue::Literal(s) if involves_extra => normalize_name(s),
+            MarkerValue::Literal(s) => s.clone(),
+            MarkerValue::Variable(MarkerVariable::Extra) => extra.to_string(),
+            MarkerValue::Variable(variable) => environment.get(*variable).to_string(),
+        };
+        compare(&resolve(&self.lhs), self.operator, &resolve(&self.rhs))
+    }
+}
+
+/// Compare two marker values.
+///
+/// Like the `packaging` python package, comparisons use PEP 440 semantics
+/// if the left side is a version and the right side makes a valid specifier,
+/// so `"3.10" >= "3.8"` holds. Otherwise the values are compared as strings.
+fn compare(lhs: &str, operator: MarkerOperator, rhs: &str) -> bool {
+    let cmp = match operator {
+        MarkerOperator::In => return rhs.contains(lhs),
+        MarkerOperator::NotIn => return !rhs.contains(lhs),
+        MarkerOperator::Compare(Compare::ArbitraryEqual) => return lhs == rhs,
+        MarkerOperator::Compare(cmp) => cmp,
+    };
+    let specifier = format!("{}{}", cmp, rhs).parse::<VersionSpecifier>();
+    if let (Ok(specifier), Ok(version)) = (specifier, lhs.parse::<PEP440Version>()) {
+        return specifier.range().contains(&version);
+    }
+    match cmp {
+        Compare::Matching | Compare::ArbitraryEqual => lhs == rhs,
+        Compare::Exclusion => lhs != rhs,
+        Compare::StrictLess => lhs < rhs,
+        Compare::LessOrEqual => lhs <= rhs,
+        Compare::StrictGreater => lhs > rhs,
+        Compare::GreaterOrEqual => lhs >= rhs,
+        // there is no compatible release of a string
+        Compare::Compatible => false,
+    }
+}
+
+/// An environment marker like `sys_platform == "win32" and python_version < "3.8"`.
+///
+/// `and` binds tighter than `or`, so `a or b and c` is `Or([a, And([b, c])])`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkerTree {
+    Expression(MarkerExpression),
+    And(Vec<MarkerTree>),
+    Or(Vec<MarkerTree>),
+}
+
+impl MarkerTree {
+    /// Whether a requirement with this marker applies to `environment`
+    /// when installing the given extras of the package that requires it.
+    pub fn evaluate(&self, environment: &Environment, extras: &[String]) -> bool {
+        if extras.is_empty() {
+            return self.evaluate_extra(environment, "");
+        }
+        extras
+            .iter()
+            .any(|extra| self.evaluate_extra(environment, &normalize_name(extra)))
+    }
+
+    fn evaluate_extra(&self, environment: &Environment, extra: &str) -> bool {
+        match self {
+            Self::Expression(expression) => expression.evaluate(environment, extra),
+            Self::And(trees) => trees.iter().all(|t| t.evaluate_extra(environment, extra)),
+            Self::Or(trees) => trees.iter().any(|t| t.evaluate_extra(environment, extra)),
+        }
+    }
+}
+
+impl Display for MarkerTree {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (trees, separator) = match self {
+            Self::Expression(e) => return write!(f, "{} {} {}", e.lhs, e.operator, e.rhs),
+            Self::And(trees) => (trees, " and "),
+            Self::Or(trees) => (trees, " or "),
+        };
+        for (i, tree) in trees.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", separator)?;
+            }
+            // parenthesize whatever would otherwise be parsed into a different tree
+            match (self, tree) {
+                (_, Self::Expression(_)) | (Self::Or(_), Self::And(_)) => write!(f, "{}", tree)?,
+                _ => write!(f, "({})", tree)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MarkerTree {
+    type Err = RequirementParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cursor = Cursor::new(s);
+        let marker = cursor.parse_marker()?;
+        cursor.eat_whitespace();
+        if !cursor.at_end() {
+            return Err(cursor.expected("'and', 'or' or the end of the marker"));
+        }
+        Ok(marker)
+    }
+}
+
+impl Cursor<'_> {
+    pub(crate) fn parse_marker(&mut self) -> Result<MarkerTree, RequirementParseError> {
+        let mut trees = vec![self.parse_marker_and()?];
+        while self.eat_keyword("or") {
+            trees.push(self.parse_marker_and()?);
+        }
+        Ok(if trees.len() == 1 {
+            trees.remove(0)
+        } else {
+            MarkerTree::Or(trees)
+        })
+    }
+
+    fn parse_marker_and(&mut self) -> Result<MarkerTree, RequirementParseError> {
+        let mut trees = vec![self.parse_marker_expression()?];
+        while self.eat_keyword("and") {
+            trees.push(self.parse_marker_expression()?);
+        }
+        Ok(if trees.len() == 1 {
+            trees.remove(0)
+        } else {
+            MarkerTree::And(trees)
+        })
+    }
+
+    fn parse_marker_expression(&mut self) -> Result<MarkerTree, RequirementParseError> {
+        self.eat_whitespace();
+        if self.eat('(') {
+            let tree = self.parse_marker()?;
+            self.eat_whitespace();
+            if !self.eat(')') {
+                return Err(self.expected("')'"));
+            }
+            return Ok(tree);
+        }
+        let lhs = self.parse_marker_value()?;
+        let operator = self.parse_marker_operator()?;
+        let rhs = self.parse_marker_value()?;
+        Ok(MarkerTree::Expression(MarkerExpression {
+            lhs,
+            operator,
+            rhs,
+        }))
+    }
+
+    fn parse_marker_value(&mut self) -> Result<MarkerValue, RequirementParseError> {
+        self.eat_whitespace();
+        match self.peek() {
+            Some(quote @ ('\'' | '"')) => {
+                let start = self.pos;
+                self.eat(quote);
+                let (_, value) = self.take_while(|c| c != quote);
+                if !self.eat(quote) {
+                    return Err(self.error(
+                        start..self.pos,
+                        RequirementParseReason::Expected("a closing quote"),
+                    ));
+                }
+                Ok(MarkerValue::Literal(value.to_string()))
+            }
+            _ => {
+                let (start, name) =
+                    self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+                if name.is_empty() {
+                    return Err(self.expected("a marker variable or string"));
+                }
+                name.parse().map(MarkerValue::Variable).map_err(|_| {
+                    self.error(
+                        start..self.pos,
+                        RequirementParseReason::UnknownMarkerVariable,
+                    )
+                })
+            }
+        }
+    }
+
+    fn parse_marker_operator(&mut self) -> Result<MarkerOperator, RequirementParseError> {
+        self.eat_whitespace();
+        let (start, operator) = self.take_while(|c| "<=>!~".contains(c));
+        if !operator.is_empty() {
+            return operator.parse().map(MarkerOperator::Compare).map_err(|_| {
+                self.error(
+                    start..self.pos,
+                    RequirementParseReason::Expected("a marker operator"),
+                )
+            });
+        }
+        if self.eat_keyword("in") {
+            return Ok(MarkerOperator::In);
+        }
+        if self.eat_keyword("not") {
+            if self.eat_keyword("in") {
+                return Ok(MarkerOperator::NotIn);
+            }
+            self.eat_whitespace();
+            return Err(self.expected("'in'"));
+        }
+        Err(self.expected("a marker operator"))
+    }
+
+    /// Consume `keyword` as a whole word, possibly preceded by whitespace.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let start = self.pos;
+        self.eat_whitespace();
+        let (_, word) = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        if word == keyword {
+            true
+        } else {
+            self.pos = start;
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markers::{
+        Environment, MarkerExpression, MarkerOperator, MarkerTree, MarkerValue, MarkerVariable,
+    };
+    use crate::ranges::{Compare, RequirementParseReason};
+    use proptest::prelude::*;
+
+    fn windows() -> Environment {
+        Environment {
+            os_name: "nt".into(),
+            platform_machine: "AMD64".into(),
+            platform_system: "Windows".into(),
+            sys_platform: "win32".into(),
+            ..Environment::default()
+        }
+    }
+
+    fn evaluate(marker: &str, environment: &Environment) -> bool {
+        marker
+            .parse::<MarkerTree>()
+            .unwrap()
+            .evaluate(environment, &[])
+    }
+
+    #[test]
+    fn environment_from_markers() {
+        let environment = Environment::from_markers(vec![
+            ("python_full_version", "3.7.4"),
+            ("sys_platform", "win32"),
+            ("platform_machine", "AMD64"),
+        ])
+        .unwrap();
+        assert_eq!(environment.python_version, "3.7");
+        assert_eq!(environment.implementation_version, "3.7.4");
+        assert_eq!(environment.sys_platform, "win32");
+        assert_eq!(environment.platform_machine, "AMD64");
+        assert_eq!(environment.os_name, Environment::default().os_name);
+        assert_eq!(
+            Environment::from_markers(vec![("extra", "x")]),
+            Err("extra".to_string())
+        );
+        assert_eq!(
+            Environment::from_markers(vec![("python_flavor", "x")]),
+            Err("python_flavor".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_expression() {
+        let marker: MarkerTree = "python_version<'3.8'".parse().unwrap();
+        assert_eq!(
+            marker,
+            MarkerTree::Expression(MarkerExpression {
+                lhs: MarkerValue::Variable(MarkerVariable::PythonVersion),
+                operator: MarkerOperator::Compare(Compare::StrictLess),
+                rhs: MarkerValue::Literal("3.8".into()),
+            })
+        );
+        assert_eq!(marker.to_string(), "python_version < \"3.8\"");
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let marker: MarkerTree = "os_name == 'nt' or sys_platform == 'linux' and extra == 'x'"
+            .parse()
+            .unwrap();
+        assert!(matches!(&marker, MarkerTree::Or(trees) if matches!(trees[1], MarkerTree::And(_))));
+        let marker: MarkerTree = "(os_name == 'nt' or sys_platform == 'linux') and extra == 'x'"
+            .parse()
+            .unwrap();
+        assert!(matches!(&marker, MarkerTree::And(trees) if matches!(trees[0], MarkerTree::Or(_))));
+        assert_eq!(
+            marker.to_string(),
+            "(os_name == \"nt\" or sys_platform == \"linux\") and extra == \"x\""
+        );
+    }
+
+    #[test]
+    fn evaluate_platform() {
+        let marker = "sys_platform == 'win32'";
+        assert!(!evaluate(marker, &Environment::default()));
+        assert!(evaluate(marker, &windows()));
+        let marker =
+            "platform_machine == 'x86_64' and (os_name == 'nt' or sys_platform == 'linux')";
+        assert!(evaluate(marker, &Environment::default()));
+        assert!(!evaluate(marker, &windows()));
+        assert!(evaluate("'linux' in sys_platform", &Environment::default()));
+        assert!(evaluate(
+            "sys_platform not in 'darwin win32'",
+            &Environment::default()
+        ));
+        assert!(evaluate("os.name == 'posix'", &Environment::default()));
+    }
+
+    #[test]
+    fn evaluate_python_version() {
+        let python36 = Environment::default().with_python_version("3.6.1");
+        assert_eq!(python36.python_version, "3.6");
+        for (marker, on_36, on_39) in &[
+            ("python_version < '3.7'", true, false),
+            ("python_version < \"3.8\"", true, false),
+            ("python_version < '3.10'", true, true),
+            ("python_version >= '3'", true, true),
+            ("python_version ~= '3.7'", false, true),
+            ("python_version == '3.*'", true, true),
+            ("python_full_version >= '3.6.2'", false, true),
+            ("python_full_version === '3.6.1'", true, false),
+            ("implementation_name == 'cpython'", true, true),
+        ] {
+            assert_eq!(evaluate(marker, &python36), *on_36, "{}", marker);
+            assert_eq!(
+                evaluate(marker, &Environment::default()),
+                *on_39,
+                "{}",
+                marker
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_string_fallback() {
+        let environment = Environment {
+            platform_release: "5.4.0-42-generic".into(),
+            ..Environment::default()
+        };
+        assert!(evaluate("platform_release >= '5.10'", &environment));
+        assert!(evaluate("platform_release != '5.10'", &environment));
+        assert!(!evaluate("platform_release ~= '5.10'", &environment));
+    }
+
+    #[test]
+    fn evaluate_extras() {
+        let marker: MarkerTree = "extra == 'Socks_Proxy'".parse().unwrap();
+        let environment = Environment::default();
+        assert!(!marker.evaluate(&environment, &[]));
+        assert!(marker.evaluate(&environment, &["socks-proxy".into()]));
+        assert!(marker.evaluate(&environment, &["security".into(), "socks.proxy".into()]));
+        assert!(!marker.evaluate(&environment, &["security".into()]));
+    }
+
+    #[test]
+    fn parse_invalid_markers() {
+        for (input, span, reason) in vec![
+            (
+                "",
+                0..0,
+                RequirementParseReason::Expected("a marker variable or string"),
+            ),
+            (
+                "os_name",
+                7..7,
+                RequirementParseReason::Expected("a marker operator"),
+            ),
+            (
+                "os_name = 'nt'",
+                8..9,
+                RequirementParseReason::Expected("a marker operator"),
+            ),
+            (
+                "os_name not 'nt'",
+                12..13,
+                RequirementParseReason::Expected("'in'"),
+            ),
+            (
+                "os_name == 'nt",
+                11..14,
+                RequirementParseReason::Expected("a closing quote"),
+            ),
+            (
+                "os == 'nt'",
+                0..2,
+                RequirementParseReason::UnknownMarkerVariable,
+            ),
+            (
+                "(os_name == 'nt'",
+                16..16,
+                RequirementParseReason::Expected("')'"),
+            ),
+            (
+                "os_name == 'nt' and",
+                19..19,
+                RequirementParseReason::Expected("a marker variable or string"),
+            ),
+            (
+                "os_name == 'nt' nor sys_platform == 'linux'",
+                16..17,
+                RequirementParseReason::Expected("'and', 'or' or the end of the marker"),
+            ),
+        ] {
+            let error = input.parse::<MarkerTree>().unwrap_err();
+            assert_eq!((input, error.span, error.reason), (input, span, reason));
+        }
+    }
+
+    fn marker_expression() -> impl Strategy<Value = MarkerTree> {
+        let variable = prop::sample::select(vec![
+            MarkerVariable::PythonVersion,
+            MarkerVariable::SysPlatform,
+            MarkerVariable::Extra,
+        ])
+        .prop_map(MarkerValue::Variable);
+        let literal = "[a-z0-9. \"]{0,6}|[a-z0-9. ']{0,6}".prop_map(MarkerValue::Literal);
+        let operator = prop::sample::select(vec![
+            MarkerOperator::Compare(Compare::Matching),
+            MarkerOperator::Compare(Compare::StrictLess),
+            MarkerOperator::Compare(Compare::ArbitraryEqual),
+            MarkerOperator::In,
+            MarkerOperator::NotIn,
+        ]);
+        (
+            prop_oneof![variable.clone(), literal.clone()],
+            operator,
+            prop_oneof![variable, literal],
+        )
+            .prop_map(|(lhs, operator, rhs)| {
+                MarkerTree::Expression(MarkerExpression { lhs, operator, rhs })
+            })
+    }
+
+    fn marker_tree() -> impl Strategy<Value = MarkerTree> {
+        marker_expression().prop_recursive(3, 12, 3, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 2..4).prop_map(MarkerTree::And),
+                prop::collection::vec(inner, 2..4).prop_map(MarkerTree::Or),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn display_round_trip(marker in marker_tree()) {
+            let formatted = marker.to_string();
+            prop_assert_eq!(formatted.parse::<MarkerTree>(), Ok(marker), "{}", formatted);
+        }
+
+        #[test]
+        fn parse_never_panics(input in "[a-z_. ()'\"<=>!~]*") {
+            let _ = input.parse::<MarkerTree>();
+        }
+    }
+}
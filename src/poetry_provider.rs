@@ -1,4 +1,7 @@
+use crate::index::{PackageIndex, PypiJsonIndex};
+use crate::markers::Environment;
 use crate::provider::PypiProvider;
+use crate::ranges::PrereleasePolicy;
 use crate::version::PEP440Version;
 use pubgrub::package::Package;
 use pubgrub::range::Range;
@@ -14,21 +17,48 @@ pub struct RootPackage<P: Package, V: Version> {
     pub dependencies: DependencyConstraints<P, V>,
 }
 
-pub struct PoetryProvider {
-    remote: PypiProvider,
+pub struct PoetryProvider<I = PypiJsonIndex> {
+    remote: PypiProvider<I>,
     root: RootPackage<String, PEP440Version>,
 }
 
-impl PoetryProvider {
-    pub fn new(root: RootPackage<String, PEP440Version>) -> Self {
+impl<I: PackageIndex> PoetryProvider<I> {
+    /// Provide `root` itself and all other packages through `remote`.
+    pub fn from_remote(root: RootPackage<String, PEP440Version>, remote: PypiProvider<I>) -> Self {
+        PoetryProvider { remote, root }
+    }
+
+    /// Resolve for the given target environment instead of the default one.
+    pub fn with_environment(self, environment: Environment) -> Self {
+        PoetryProvider {
+            remote: self.remote.with_environment(environment),
+            ..self
+        }
+    }
+
+    /// Pick pre-releases according to `prereleases`.
+    pub fn with_prereleases(self, prereleases: PrereleasePolicy) -> Self {
         PoetryProvider {
-            remote: PypiProvider::default(),
-            root,
+            remote: self.remote.with_prereleases(prereleases),
+            ..self
         }
     }
+
+    /// Only pick releases supporting all of `python`, see [PypiProvider::with_python].
+    pub fn with_python(self, python: Range<PEP440Version>) -> Self {
+        PoetryProvider {
+            remote: self.remote.with_python(python),
+            ..self
+        }
+    }
+
+    /// See [PypiProvider::yanked_reason].
+    pub fn yanked_reason(&self, package: &str, version: &PEP440Version) -> Option<String> {
+        self.remote.yanked_reason(package, version)
+    }
 }
 
-impl DependencyProvider<String, PEP440Version> for PoetryProvider {
+impl<I: PackageIndex> DependencyProvider<String, PEP440Version> for PoetryProvider<I> {
     fn choose_package_version<T: Borrow<String>, U: Borrow<Range<PEP440Version>>>(
         &self,
         potential_packages: impl Iterator<Item = (T, U)>,
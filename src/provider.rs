@@ -1,95 +1,303 @@
-use crate::ranges::parse_dependency;
+use crate::index::{IndexError, IndexRelease, PackageIndex, PypiJsonIndex, ReleaseFile};
+use crate::markers::Environment;
+use crate::ranges::{is_pinned, parse_specifiers, PrereleasePolicy, RequirementParseError};
+use crate::requirement::{normalize_name, split_extra_package, Requirement};
 use crate::version::PEP440Version;
 use pubgrub::range::Range;
 use pubgrub::solver::Dependencies::Known;
-use pubgrub::solver::{
-    choose_package_with_fewest_versions, Dependencies, DependencyConstraints, DependencyProvider,
-};
-use serde::Deserialize;
-use serde_json::{Map, Value};
+use pubgrub::solver::{Dependencies, DependencyConstraints, DependencyProvider};
 use std::borrow::Borrow;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::error::Error;
 
-pub struct PypiProvider {
-    client: reqwest::blocking::Client,
-    releases_cache: RefCell<HashMap<String, Vec<PEP440Version>>>,
+/// What to do with a `requires_dist` entry that cannot be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidRequirements {
+    /// Ignore the entry and keep the other dependencies of the package.
+    #[default]
+    Skip,
+    /// Fail retrieving the dependencies of the package.
+    /// The resolver always skips invalid entries like pip does, only tests ask for this.
+    #[cfg_attr(not(test), allow(dead_code))]
+    Fail,
+}
+
+/// Dependency provider for the packages of a [PackageIndex], PyPI by default.
+pub struct PypiProvider<I = PypiJsonIndex> {
+    index: I,
+    releases_cache: RefCell<HashMap<String, Vec<Release>>>,
+    /// Dependencies of the packages pubgrub asked for, which it may do several times.
+    dependencies_cache:
+        RefCell<HashMap<(String, PEP440Version), DependencyConstraints<String, PEP440Version>>>,
+    invalid_requirements: InvalidRequirements,
+    environment: Environment,
+    prereleases: PrereleasePolicy,
+    /// Python versions a release must support all of to be picked, any release if `None`.
+    python: Option<Range<PEP440Version>>,
 }
 
 impl Default for PypiProvider {
     fn default() -> Self {
+        PypiProvider::new(PypiJsonIndex::default())
+    }
+}
+
+impl<I: PackageIndex> PypiProvider<I> {
+    pub fn new(index: I) -> Self {
         PypiProvider {
-            client: reqwest::blocking::Client::new(),
+            index,
             releases_cache: RefCell::new(Default::default()),
+            dependencies_cache: RefCell::new(Default::default()),
+            invalid_requirements: Default::default(),
+            environment: Default::default(),
+            prereleases: Default::default(),
+            python: None,
+        }
+    }
+
+    /// Only follow dependencies whose environment marker applies to `environment`.
+    pub fn with_environment(self, environment: Environment) -> Self {
+        PypiProvider {
+            environment,
+            ..self
         }
     }
+
+    /// Pick pre-releases according to `prereleases`.
+    pub fn with_prereleases(self, prereleases: PrereleasePolicy) -> Self {
+        PypiProvider {
+            prereleases,
+            ..self
+        }
+    }
+
+    /// Only pick releases whose `Requires-Python` allows all of `python`,
+    /// which is either the target interpreter version or the python range of the project.
+    pub fn with_python(self, python: Range<PEP440Version>) -> Self {
+        PypiProvider {
+            python: Some(python),
+            ..self
+        }
+    }
+
+    /// Releases of `package` that can be picked, oldest first.
+    /// Virtual packages for extras have the releases of their base package.
+    fn releases(&self, package: &str) -> Result<Vec<Release>, Box<dyn Error>> {
+        let (package, _) = split_extra_package(package);
+        if let Some(releases) = self.releases_cache.borrow().get(package) {
+            return Ok(releases.clone());
+        }
+        let releases = available_versions(&self.index.releases(package)?, self.python.as_ref());
+        self.releases_cache
+            .borrow_mut()
+            .insert(package.to_string(), releases.clone());
+        Ok(releases)
+    }
+
+    /// If `version` of `package` was yanked, the reason given for it or an empty string.
+    /// Only releases that were listed while resolving are known.
+    pub fn yanked_reason(&self, package: &str, version: &PEP440Version) -> Option<String> {
+        let releases = self.releases_cache.borrow();
+        let release = releases
+            .get(split_extra_package(package).0)?
+            .iter()
+            .find(|release| &release.version == version)?;
+        if !release.yanked {
+            return None;
+        }
+        Some(release.yanked_reason.clone().unwrap_or_default())
+    }
 }
 
-#[derive(Deserialize, Debug, Clone)]
-struct PypiPackage {
-    info: PackageInfo,
-    releases: Map<String, Value>,
+/// A release of a package that can be picked.
+#[derive(Debug, Clone, PartialEq)]
+struct Release {
+    version: PEP440Version,
+    /// Whether all files of the release were yanked, see
+    /// [PEP 592](https://www.python.org/dev/peps/pep-0592/).
+    yanked: bool,
+    yanked_reason: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
-struct PackageInfo {
-    requires_dist: Option<Vec<String>>,
+impl Release {
+    fn new(version: PEP440Version, files: &[ReleaseFile]) -> Self {
+        Release {
+            version,
+            yanked: !files.is_empty() && files.iter().all(|file| file.yanked),
+            yanked_reason: files.iter().find_map(|file| file.yanked_reason.clone()),
+        }
+    }
 }
 
-fn get_deps(
-    client: &reqwest::blocking::Client,
-    package: &String,
-    version: &PEP440Version,
-) -> Result<DependencyConstraints<String, PEP440Version>, Box<dyn Error>> {
-    let url = format!("https://pypi.org/pypi/{}/{}/json", package, version);
-    let response = client.get(&url).send()?;
-    let package: PypiPackage = response.json()?;
-    let deps = package
-        .info
-        .requires_dist
-        .unwrap_or(Default::default())
+/// Whether a file with this `Requires-Python` can be installed for all of `python`.
+/// Invalid values don't exclude anything, like in pip.
+/// Only the PEP 440 versions of `python` count, as ranges like `*` or `<4`
+/// also contain arbitrary versions that no `Requires-Python` but `===` matches.
+fn supports_python(requires_python: Option<&str>, python: &Range<PEP440Version>) -> bool {
+    let python = python.intersection(&Range::higher_than(PEP440Version::lowest_pep440()));
+    match requires_python.map(parse_specifiers) {
+        Some(Ok(supported)) => supported.intersection(&python) == python,
+        _ => true,
+    }
+}
+
+/// Releases sorted by version, leaving out those without any file supporting `python`.
+fn available_versions(
+    releases: &[IndexRelease],
+    python: Option<&Range<PEP440Version>>,
+) -> Vec<Release> {
+    let supported = |files: &[ReleaseFile]| match python {
+        Some(python) => {
+            files.is_empty()
+                || files
+                    .iter()
+                    .any(|file| supports_python(file.requires_python.as_deref(), python))
+        }
+        None => true,
+    };
+    let mut available: Vec<Release> = releases
         .iter()
-        .filter_map(|v| parse_dependency(v.as_str()))
+        .filter(|release| supported(&release.files))
+        .flat_map(|release| {
+            release_versions(&release.version)
+                .map(move |version| Release::new(version, &release.files))
+        })
         .collect();
+    available.sort_by(|a, b| a.version.cmp(&b.version));
+    available
+}
+
+/// Versions a published version string can be matched as.
+/// Every release can be matched by arbitrary equality (`===`) with its exact string,
+/// valid PEP 440 versions can also be matched by all other specifiers.
+fn release_versions(release: &str) -> impl Iterator<Item = PEP440Version> {
+    release
+        .parse::<PEP440Version>()
+        .ok()
+        .into_iter()
+        .chain(std::iter::once(PEP440Version::from_arbitrary(release)))
+}
+
+/// Same as [pubgrub::solver::choose_package_with_fewest_versions],
+/// but only counts and picks the [candidates](PrereleasePolicy::candidates) of the range.
+/// Yanked releases are only candidates if the range [pins](is_pinned) them.
+fn choose_package_with_fewest_versions<T, U, I, F>(
+    list_available_versions: F,
+    prereleases: &PrereleasePolicy,
+    potential_packages: impl Iterator<Item = (T, U)>,
+) -> (T, Option<PEP440Version>)
+where
+    T: Borrow<String>,
+    U: Borrow<Range<PEP440Version>>,
+    I: Iterator<Item = Release>,
+    F: Fn(&String) -> I,
+{
+    let candidates = |package: &String, range: &Range<PEP440Version>| {
+        let pinned = is_pinned(range);
+        let releases = list_available_versions(package)
+            .filter(|release| pinned || !release.yanked)
+            .map(|release| release.version);
+        prereleases.candidates(package, range, releases)
+    };
+    let count_valid = |(p, range): &(T, U)| candidates(p.borrow(), range.borrow()).len();
+    let (pkg, range) = potential_packages
+        .min_by_key(count_valid)
+        .expect("potential_packages gave us an empty iterator");
+    let version = candidates(pkg.borrow(), range.borrow()).into_iter().next();
+    (pkg, version)
+}
+
+/// Turn `requires_dist` entries into dependency constraints.
+///
+/// Without an extra, these are the entries whose marker applies to `environment`.
+/// With an extra, these are only the entries the extra adds to them.
+/// Ranges of a package listed more than once are intersected.
+/// A direct reference fails even when skipping invalid requirements,
+/// since leaving it out would silently drop the dependency.
+fn parse_requires_dist(
+    requires_dist: &[String],
+    extra: Option<&str>,
+    environment: &Environment,
+    invalid_requirements: InvalidRequirements,
+) -> Result<DependencyConstraints<String, PEP440Version>, RequirementParseError> {
+    let extras: Vec<String> = extra.map(String::from).into_iter().collect();
+    let mut deps = DependencyConstraints::default();
+    for requirement in requires_dist {
+        let requirement: Requirement = match requirement.parse() {
+            Ok(requirement) => requirement,
+            Err(_) if invalid_requirements == InvalidRequirements::Skip => continue,
+            Err(e) => return Err(e),
+        };
+        let applies = requirement.applies_to(environment, &extras)
+            && (extra.is_none() || !requirement.applies_to(environment, &[]));
+        if !applies {
+            continue;
+        }
+        for (name, range) in requirement.dependencies()? {
+            let range = match deps.get(&name) {
+                Some(existing) => range.intersection(existing),
+                None => range,
+            };
+            deps.insert(name, range);
+        }
+    }
     Ok(deps)
 }
 
-impl DependencyProvider<String, PEP440Version> for PypiProvider {
+/// Dependencies of `package` at `version` given its `requires_dist`.
+/// A [virtual package](crate::requirement::extra_package) for an extra
+/// also depends on exactly the same version of its base package.
+fn package_dependencies(
+    package: &str,
+    version: &PEP440Version,
+    requires_dist: &[String],
+    environment: &Environment,
+    invalid_requirements: InvalidRequirements,
+) -> Result<DependencyConstraints<String, PEP440Version>, RequirementParseError> {
+    let (base, extra) = split_extra_package(package);
+    let mut deps = parse_requires_dist(requires_dist, extra, environment, invalid_requirements)?;
+    if extra.is_some() {
+        deps.insert(normalize_name(base), Range::exact(version.clone()));
+    }
+    Ok(deps)
+}
+
+fn get_deps(
+    index: &impl PackageIndex,
+    package: &str,
+    version: &PEP440Version,
+    environment: &Environment,
+    invalid_requirements: InvalidRequirements,
+) -> Result<DependencyConstraints<String, PEP440Version>, Box<dyn Error>> {
+    let (base, _) = split_extra_package(package);
+    let requires_dist = index.requires_dist(base, version)?;
+    Ok(package_dependencies(
+        package,
+        version,
+        &requires_dist,
+        environment,
+        invalid_requirements,
+    )?)
+}
+
+impl<I: PackageIndex> DependencyProvider<String, PEP440Version> for PypiProvider<I> {
     fn choose_package_version<T: Borrow<String>, U: Borrow<Range<PEP440Version>>>(
         &self,
         potential_packages: impl Iterator<Item = (T, U)>,
     ) -> Result<(T, Option<PEP440Version>), Box<dyn Error>> {
-        let list_available_versions = |package: &String| {
-            let versions: Vec<PEP440Version> = self
-                .releases_cache
-                .borrow_mut()
-                .entry(package.clone())
-                .or_insert_with(|| {
-                    let url = format!("https://pypi.org/pypi/{}/json", package);
-                    let mut versions: Vec<PEP440Version> = self
-                        .client
-                        .get(&url)
-                        .send()
-                        .and_then(|response| {
-                            let data: PypiPackage = response.json()?;
-                            Ok(data
-                                .releases
-                                .keys()
-                                .filter_map(|v| v.parse::<PEP440Version>().ok())
-                                .collect())
-                        })
-                        .unwrap_or(Default::default());
-                    versions.sort();
-                    versions
-                })
-                .clone();
-            versions.into_iter().rev()
-        };
+        let potential_packages: Vec<_> = potential_packages.collect();
+        for (package, _) in &potential_packages {
+            self.releases(package.borrow())?;
+        }
+        // all releases were listed above
+        let list_available_versions =
+            |package: &String| self.releases(package).unwrap_or_default().into_iter().rev();
 
         Ok(choose_package_with_fewest_versions(
             list_available_versions,
-            potential_packages,
+            &self.prereleases,
+            potential_packages.into_iter(),
         ))
     }
 
@@ -98,16 +306,395 @@ impl DependencyProvider<String, PEP440Version> for PypiProvider {
         package: &String,
         version: &PEP440Version,
     ) -> Result<Dependencies<String, PEP440Version>, Box<dyn Error>> {
-        let deps = Known(get_deps(&self.client, package, version)?);
-        Ok(deps)
+        let key = (package.clone(), version.clone());
+        if let Some(deps) = self.dependencies_cache.borrow().get(&key) {
+            return Ok(Known(deps.clone()));
+        }
+        let deps = match get_deps(
+            &self.index,
+            package,
+            version,
+            &self.environment,
+            self.invalid_requirements,
+        ) {
+            Ok(deps) => deps,
+            // without its requirements the release cannot be used, but others of the package can
+            Err(e) if matches!(e.downcast_ref(), Some(IndexError::NoMetadataFile { .. })) => {
+                return Ok(Dependencies::Unknown)
+            }
+            Err(e) => return Err(e),
+        };
+        self.dependencies_cache
+            .borrow_mut()
+            .insert(key, deps.clone());
+        Ok(Known(deps))
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::provider::PypiProvider;
+    use crate::index::{IndexError, IndexRelease, PackageIndex, ReleaseFile};
+    use crate::markers::Environment;
+    use crate::provider::{
+        available_versions, choose_package_with_fewest_versions, package_dependencies,
+        parse_requires_dist, release_versions, supports_python, InvalidRequirements, PypiProvider,
+        Release,
+    };
+    use crate::ranges::{parse_poetry_constraint, parse_specifier, PrereleasePolicy};
+    use crate::requirement::normalize_name;
     use crate::version::PEP440Version;
-    use pubgrub::solver::resolve;
+    use pubgrub::range::Range;
+    use pubgrub::solver::{resolve, DependencyProvider};
+    use std::cell::Cell;
+    use std::collections::HashMap;
+    use std::error::Error;
+
+    #[test]
+    fn test_release_versions() {
+        let versions: Vec<_> = release_versions("1.0").collect();
+        assert_eq!(
+            versions,
+            vec![PEP440Version::one(), PEP440Version::from_arbitrary("1.0")]
+        );
+        let versions: Vec<_> = release_versions("2004d").collect();
+        assert_eq!(versions, vec![PEP440Version::from_arbitrary("2004d")]);
+    }
+
+    /// An index serving releases and `Requires-Dist` from memory.
+    #[derive(Default)]
+    struct MemoryIndex {
+        releases: HashMap<String, Vec<IndexRelease>>,
+        requires_dist: HashMap<(String, String), Vec<String>>,
+        /// How often `requires_dist` was asked for.
+        lookups: Cell<usize>,
+    }
+
+    impl MemoryIndex {
+        fn add(&mut self, package: &str, version: &str, requires_dist: &[&str]) {
+            let package = normalize_name(package);
+            self.releases
+                .entry(package.clone())
+                .or_default()
+                .push(IndexRelease {
+                    version: version.to_string(),
+                    files: vec![ReleaseFile::default()],
+                });
+            let requires_dist = requires_dist.iter().map(|r| r.to_string()).collect();
+            self.requires_dist
+                .insert((package, version.to_string()), requires_dist);
+        }
+    }
+
+    impl PackageIndex for MemoryIndex {
+        fn releases(&self, package: &str) -> Result<Vec<IndexRelease>, Box<dyn Error>> {
+            self.releases
+                .get(&normalize_name(package))
+                .cloned()
+                .ok_or_else(|| {
+                    let package = package.to_string();
+                    IndexError::NotFound {
+                        package,
+                        version: None,
+                    }
+                    .into()
+                })
+        }
+
+        fn requires_dist(
+            &self,
+            package: &str,
+            version: &PEP440Version,
+        ) -> Result<Vec<String>, Box<dyn Error>> {
+            self.lookups.set(self.lookups.get() + 1);
+            self.requires_dist
+                .get(&(normalize_name(package), version.to_string()))
+                .cloned()
+                .ok_or_else(|| format!("{} {} is not in the index", package, version).into())
+        }
+    }
+
+    #[test]
+    fn test_dependencies_are_looked_up_once() {
+        let mut index = MemoryIndex::default();
+        index.add("foo", "1.0", &["bar (>=1)", "baz ; extra == 'x'"]);
+        let provider = PypiProvider::new(index);
+        let one: PEP440Version = "1.0".parse().unwrap();
+        for package in &["foo", "foo", "foo[x]", "foo[x]"] {
+            provider
+                .get_dependencies(&package.to_string(), &one)
+                .unwrap();
+        }
+        assert_eq!(provider.index.lookups.get(), 2);
+    }
+
+    #[test]
+    fn test_index_errors_are_not_taken_for_missing_releases() {
+        let provider = PypiProvider::new(MemoryIndex::default());
+        let range = Range::<PEP440Version>::any();
+        let error = provider
+            .choose_package_version(vec![("foo".to_string(), range)].into_iter())
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(IndexError::NotFound { package, .. }) if package == "foo"
+        ));
+    }
+
+    /// Releases from a JSON object mapping versions to their files, like PyPI's `releases`.
+    fn parse_releases(json: &str) -> Vec<IndexRelease> {
+        let releases: HashMap<String, Vec<ReleaseFile>> = serde_json::from_str(json).unwrap();
+        releases
+            .into_iter()
+            .map(|(version, files)| IndexRelease { version, files })
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_from_index() {
+        let mut index = MemoryIndex::default();
+        index.add(
+            "myapp",
+            "1.0",
+            &["requests[socks] (>=2.20)", "pysocks (<2)"],
+        );
+        index.add("requests", "2.19.0", &[]);
+        index.add(
+            "requests",
+            "2.25.1",
+            &["idna (<3,>=2.5)", "PySocks (>=1.5.6) ; extra == 'socks'"],
+        );
+        index.add("idna", "2.10", &[]);
+        index.add("idna", "3.1", &[]);
+        index.add("PySocks", "1.7.1", &[]);
+        let provider = PypiProvider::new(index);
+        let solution = resolve(&provider, "myapp".into(), PEP440Version::one()).unwrap();
+        let version = |package: &str| solution.get(package).map(|v| v.to_string());
+        assert_eq!(version("requests"), Some("2.25.1".into()));
+        assert_eq!(version("requests[socks]"), Some("2.25.1".into()));
+        assert_eq!(version("idna"), Some("2.10".into()));
+        // both spellings of PySocks are the same package
+        assert_eq!(version("pysocks"), Some("1.7.1".into()));
+        assert_eq!(version("PySocks"), None);
+    }
+
+    /// Releases that were not yanked, newest first.
+    fn releases(versions: &[&str]) -> impl Iterator<Item = Release> {
+        let mut releases: Vec<Release> = versions
+            .iter()
+            .flat_map(|v| release_versions(v))
+            .map(|version| Release::new(version, &[]))
+            .collect();
+        releases.sort_by(|a, b| b.version.cmp(&a.version));
+        releases.into_iter()
+    }
+
+    #[test]
+    fn test_choose_arbitrary_only_with_arbitrary_equality() {
+        let versions = || releases(&["0.9", "1.0", "legacy-2"]);
+        let package = "pkg".to_string();
+        let range = parse_specifier("<2").unwrap();
+        let (_, version) = choose_package_with_fewest_versions(
+            |_: &String| versions(),
+            &PrereleasePolicy::default(),
+            std::iter::once((&package, &range)),
+        );
+        assert_eq!(version, Some(PEP440Version::one()));
+        let range = parse_specifier("===legacy-2").unwrap();
+        let (_, version) = choose_package_with_fewest_versions(
+            |_: &String| versions(),
+            &PrereleasePolicy::default(),
+            std::iter::once((&package, &range)),
+        );
+        assert_eq!(version, Some(PEP440Version::from_arbitrary("legacy-2")));
+    }
+
+    #[test]
+    fn test_choose_final_release_over_prerelease() {
+        let versions = || releases(&["3.0.0rc1", "2.25.1", "2.25.0"]);
+        let package = "requests".to_string();
+        let range = parse_specifier(">=2").unwrap();
+        let choose = |prereleases: &PrereleasePolicy| {
+            choose_package_with_fewest_versions(
+                |_: &String| versions(),
+                prereleases,
+                std::iter::once((&package, &range)),
+            )
+            .1
+        };
+        assert_eq!(choose(&PrereleasePolicy::default()), "2.25.1".parse().ok());
+        let allowed = PrereleasePolicy::default().allow("requests");
+        assert_eq!(choose(&allowed), "3.0.0rc1".parse().ok());
+        let range = parse_specifier(">=3.0.0rc1").unwrap();
+        let (_, version) = choose_package_with_fewest_versions(
+            |_: &String| releases(&["3.0.0rc1", "2.25.1"]),
+            &PrereleasePolicy {
+                fallback: false,
+                ..PrereleasePolicy::default()
+            },
+            std::iter::once((&package, &range)),
+        );
+        assert_eq!(version, "3.0.0rc1".parse().ok());
+    }
+
+    #[test]
+    fn test_requires_python() {
+        let releases = parse_releases(
+            r#"{
+                "1.0": [{"requires_python": null}],
+                "2.0": [
+                    {"requires_python": ">=3.6"},
+                    {"requires_python": ">=2.7, !=3.0.*, !=3.1.*"}
+                ],
+                "3.0": [{"requires_python": ">=3.7,<3.10"}],
+                "4.0": [{"requires_python": ">=3.8"}],
+                "5.0": [{"requires_python": "not a specifier"}],
+                "6.0": []
+            }"#,
+        );
+        let versions = |python: Option<&str>| {
+            let python = python.map(|python| parse_poetry_constraint(python).unwrap());
+            let versions = available_versions(&releases, python.as_ref());
+            versions
+                .iter()
+                .filter(|release| !release.version.is_arbitrary())
+                .map(|release| release.version.to_string())
+                .collect::<Vec<_>>()
+        };
+        let all = vec!["1.0", "2.0", "3.0", "4.0", "5.0", "6.0"];
+        assert_eq!(versions(None), all);
+        assert_eq!(versions(Some("==3.9.0")), all);
+        assert_eq!(versions(Some("==3.6.9")), vec!["1.0", "2.0", "5.0", "6.0"]);
+        assert_eq!(versions(Some("^3.7")), vec!["1.0", "2.0", "5.0", "6.0"]);
+        assert_eq!(
+            versions(Some(">=3.7,<3.10")),
+            vec!["1.0", "2.0", "3.0", "5.0", "6.0"]
+        );
+        // any python, or any below 3.10, is more than what 2.0 and later support
+        assert_eq!(versions(Some("*")), vec!["1.0", "5.0", "6.0"]);
+        assert_eq!(versions(Some("<3.10")), vec!["1.0", "5.0", "6.0"]);
+        // arbitrary versions in ranges without lower bound don't need to be supported
+        let python = parse_poetry_constraint("<3.10").unwrap();
+        assert!(supports_python(Some(">=0.dev0,<3.10"), &python));
+        let python = parse_poetry_constraint("*").unwrap();
+        assert!(supports_python(Some(">=0.dev0"), &python));
+        assert!(!supports_python(Some(">=3.6"), &python));
+    }
+
+    #[test]
+    fn test_yanked_releases() {
+        let mut index = MemoryIndex::default();
+        index.releases.insert(
+            "pkg".into(),
+            parse_releases(
+                r#"{
+                    "1.0": [{"yanked": false, "yanked_reason": null}],
+                    "1.1": [
+                        {"yanked": true, "yanked_reason": "broken wheel"},
+                        {"yanked": false, "yanked_reason": null}
+                    ],
+                    "2.0": [
+                        {"yanked": true, "yanked_reason": "security issue"},
+                        {"yanked": true, "yanked_reason": null}
+                    ],
+                    "2.1": [{"yanked": true}]
+                }"#,
+            ),
+        );
+        let provider = PypiProvider::new(index);
+        let package = "pkg".to_string();
+        let choose = |range: &str| {
+            let range = parse_specifier(range).unwrap();
+            provider
+                .choose_package_version(std::iter::once((&package, &range)))
+                .unwrap()
+                .1
+                .map(|version| version.to_string())
+        };
+        assert_eq!(choose(">=1.0"), Some("1.1".into()));
+        assert_eq!(choose(">=2.0"), None);
+        assert_eq!(choose("==2.0"), Some("2.0".into()));
+        assert_eq!(choose("===2.1"), Some("2.1".into()));
+        assert_eq!(choose("==2.*"), None);
+        let version = |v: &str| v.parse::<PEP440Version>().unwrap();
+        assert_eq!(provider.yanked_reason("pkg", &version("1.1")), None);
+        assert_eq!(
+            provider.yanked_reason("pkg[extra]", &version("2.0")),
+            Some("security issue".into())
+        );
+        assert_eq!(
+            provider.yanked_reason("pkg", &version("2.1")),
+            Some("".into())
+        );
+        assert_eq!(provider.yanked_reason("other", &version("2.1")), None);
+    }
+
+    #[test]
+    fn test_parse_requires_dist() {
+        let requires_dist = vec![
+            "idna (<3,>=2.5)".to_string(),
+            "idna (!=2.6)".to_string(),
+            "broken (>=>1)".to_string(),
+            "PySocks (!=1.5.7,>=1.5.6) ; extra == 'socks'".to_string(),
+            "win-inet-pton ; sys_platform == 'win32' and python_version == '2.7'".to_string(),
+            "importlib-metadata ; python_version < \"3.8\"".to_string(),
+        ];
+        let environment = Environment::default();
+        let deps = parse_requires_dist(
+            &requires_dist,
+            None,
+            &environment,
+            InvalidRequirements::Skip,
+        )
+        .unwrap();
+        assert_eq!(deps.len(), 1);
+        let idna = &deps["idna"];
+        assert!(idna.contains(&"2.5".parse().unwrap()));
+        assert!(!idna.contains(&"2.6".parse().unwrap()));
+        let python37 = Environment::default().with_python_version("3.7.9");
+        let deps = parse_requires_dist(&requires_dist, None, &python37, InvalidRequirements::Skip)
+            .unwrap();
+        assert_eq!(deps.len(), 2);
+        assert!(deps.contains_key("importlib-metadata"));
+        let error = parse_requires_dist(
+            &requires_dist,
+            None,
+            &environment,
+            InvalidRequirements::Fail,
+        )
+        .unwrap_err();
+        assert_eq!(error.input, "broken (>=>1)");
+    }
+
+    #[test]
+    fn test_extra_dependencies() {
+        let requires_dist = vec![
+            "idna (<3,>=2.5)".to_string(),
+            "PySocks (!=1.5.7,>=1.5.6) ; extra == 'socks'".to_string(),
+            "pyOpenSSL (>=0.14) ; extra == 'security'".to_string(),
+            "cryptography[ssh] (>=1.3.4) ; extra == 'Security'".to_string(),
+        ];
+        let environment = Environment::default();
+        let version = PEP440Version::new(2, 25, 0);
+        let skip = InvalidRequirements::Skip;
+        let deps =
+            package_dependencies("requests", &version, &requires_dist, &environment, skip).unwrap();
+        assert_eq!(deps.keys().collect::<Vec<_>>(), vec!["idna"]);
+        let deps = package_dependencies(
+            "requests[security]",
+            &version,
+            &requires_dist,
+            &environment,
+            skip,
+        )
+        .unwrap();
+        let mut packages: Vec<_> = deps.keys().cloned().collect();
+        packages.sort();
+        assert_eq!(
+            packages,
+            vec!["cryptography", "cryptography[ssh]", "pyopenssl", "requests"]
+        );
+        assert_eq!(deps["requests"], Range::exact(version));
+    }
 
     #[test]
     fn test_requests_1_0_0() {
@@ -120,12 +707,12 @@ mod test {
     fn test_requests_2_25_0() {
         let provider = PypiProvider::default();
         let solution = resolve(&provider, "requests".into(), PEP440Version::new(2, 25, 0)).unwrap();
-        for (p, v) in vec![
-            ("requests".into(), PEP440Version::new(2, 25, 0)),
-            ("certifi".into(), PEP440Version::new(2020, 12, 5)),
-            ("chardet".into(), PEP440Version::new(3, 0, 4)),
-            ("idna".into(), PEP440Version::new(2, 10, 0)),
-            ("urllib3".into(), PEP440Version::new(1, 26, 2)),
+        for (p, v) in [
+            ("requests", PEP440Version::new(2, 25, 0)),
+            ("certifi", PEP440Version::new(2020, 12, 5)),
+            ("chardet", PEP440Version::new(3, 0, 4)),
+            ("idna", PEP440Version::new(2, 10, 0)),
+            ("urllib3", PEP440Version::new(1, 26, 2)),
         ] {
             assert_eq!((p, solution.get(p).unwrap()), (p, &v))
         }
@@ -143,7 +730,7 @@ mod test {
     fn test_django_3_1_3() {
         let provider = PypiProvider::default();
         let solution = resolve(&provider, "django".into(), PEP440Version::new(3, 1, 3)).unwrap();
-        for (p, v) in vec![
+        for (p, v) in [
             ("asgiref", PEP440Version::new(3, 3, 1)),
             ("pytz", PEP440Version::new(2020, 4, 0)),
             ("sqlparse", PEP440Version::new(0, 4, 1)),
@@ -158,5 +745,6 @@ mod test {
         let provider = PypiProvider::default();
         let solution =
             resolve(&provider, "tensorflow".into(), PEP440Version::new(2, 3, 1)).unwrap();
+        assert!(solution.contains_key("numpy"));
     }
 }
@@ -1,16 +1,82 @@
-use crate::version::PEP440Version;
+use crate::requirement::{normalize_name, split_extra_package, Cursor};
+use crate::version::{PEP440Version, VersionParseError};
 use lazy_static::lazy_static;
 use pubgrub::range::Range;
+use pubgrub::version::Version;
 use regex::Regex;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+use thiserror::Error;
 
 lazy_static! {
     // copied from packaging python package
-    pub static ref SPECIFIER_PATTERN: Regex = Regex::new(r"^(?P<compare>~=|==|!=|<=|>=|<|>|===)\s*(?P<version>\S+)\s*$").unwrap();
-    pub static ref DEPENDENCY_PATTERN: Regex = Regex::new(r"^(?P<name>\S+)\s*(:?\((?P<specs>.+?)\))?\s*(?:;\s*(?P<extra>.*))?$").unwrap();
+    pub static ref SPECIFIER_PATTERN: Regex = Regex::new(r"^\s*(?P<compare>~=|===|==|!=|<=|>=|<|>)\s*(?P<version>\S+)\s*$").unwrap();
 }
 
-enum Compare {
+/// Error parsing a requirement or one of its version specifiers.
+#[derive(Error, Debug, PartialEq)]
+#[error("{reason} at {}..{} in '{input}'", span.start, span.end)]
+pub struct RequirementParseError {
+    /// Requirement or specifier that was being parsed.
+    pub input: String,
+    /// Byte range of `input` where parsing failed.
+    pub span: std::ops::Range<usize>,
+    /// Why parsing failed.
+    pub reason: RequirementParseReason,
+}
+
+impl RequirementParseError {
+    pub(crate) fn new(
+        input: &str,
+        span: std::ops::Range<usize>,
+        reason: RequirementParseReason,
+    ) -> Self {
+        RequirementParseError {
+            input: input.to_string(),
+            span,
+            reason,
+        }
+    }
+
+    /// Report an error in a part of `input` starting at `offset` against the whole of `input`.
+    pub(crate) fn within(self, input: &str, offset: usize) -> Self {
+        RequirementParseError {
+            input: input.to_string(),
+            span: self.span.start + offset..self.span.end + offset,
+            reason: self.reason,
+        }
+    }
+}
+
+/// Reason of a [RequirementParseError].
+#[derive(Error, Debug, PartialEq)]
+pub enum RequirementParseReason {
+    #[error("invalid package name")]
+    InvalidName,
+    #[error("invalid extra name")]
+    InvalidExtra,
+    #[error("expected {0}")]
+    Expected(&'static str),
+    #[error("invalid version specifier")]
+    InvalidSpecifier,
+    #[error(transparent)]
+    InvalidVersion(#[from] VersionParseError),
+    #[error("'{0}' does not allow a wildcard version")]
+    UnexpectedWildcard(Compare),
+    #[error("a wildcard version may only contain an epoch and a release")]
+    InvalidWildcard,
+    #[error("'{0}' does not allow a local version label")]
+    UnexpectedLocal(Compare),
+    #[error("'~=' needs a version with at least two release components")]
+    CompatibleReleaseTooShort,
+    #[error("unknown marker variable")]
+    UnknownMarkerVariable,
+    #[error("direct references are not supported, only releases from the index")]
+    DirectReference,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
     Compatible,
     Matching,
     Exclusion,
@@ -40,75 +106,679 @@ impl FromStr for Compare {
     }
 }
 
-fn compare_to_range(cmp: Compare, version: PEP440Version) -> Range<PEP440Version> {
-    match cmp {
+impl Display for Compare {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Compatible => "~=",
+            Self::Matching => "==",
+            Self::Exclusion => "!=",
+            Self::LessOrEqual => "<=",
+            Self::GreaterOrEqual => ">=",
+            Self::StrictLess => "<",
+            Self::StrictGreater => ">",
+            Self::ArbitraryEqual => "===",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Versions matching `==version`.
+/// Local labels of candidates are ignored unless the specifier has one itself.
+fn matching_range(version: PEP440Version) -> Range<PEP440Version> {
+    if version.local.is_empty() {
+        let upper = version.bump_public();
+        Range::between(version, upper)
+    } else {
+        Range::exact(version)
+    }
+}
+
+/// Versions matching `<version`.
+/// As PEP 440 asks, pre-releases of `version` are excluded unless it is one itself,
+/// so `<2.0` does not match `2.0rc1` and `<2.0.post1` matches neither `2.0rc1` nor `2.0.post1.dev0`.
+/// Dev releases of earlier post releases like `2.0.post0.dev0` are still matched.
+fn lower_than(version: PEP440Version) -> Range<PEP440Version> {
+    if version.is_prerelease() {
+        return Range::strictly_lower_than(version);
+    }
+    let first_dev = |version: &PEP440Version| PEP440Version {
+        dev: Some(0),
+        ..version.clone()
+    };
+    if version.is_postrelease() {
+        let release = PEP440Version {
+            post: None,
+            ..version.clone()
+        };
+        return Range::strictly_lower_than(first_dev(&release))
+            .union(&Range::between(release, first_dev(&version)));
+    }
+    Range::strictly_lower_than(first_dev(&version))
+}
+
+/// Versions matching `>version`.
+/// As PEP 440 asks, post releases of `version` are excluded unless it is one itself,
+/// so `>1.7` does not match `1.7.post1`.
+fn higher_than(version: PEP440Version) -> Range<PEP440Version> {
+    if version.is_postrelease() || version.dev.is_some() {
+        return Range::higher_than(version.bump_public());
+    }
+    Range::higher_than(version.bump_post_releases())
+}
+
+/// Versions whose release starts with `prefix`, like `==1.4.*`.
+/// Missing release components count as zeros, so `1.4` matches `==1.4.0.*`.
+fn prefix_range(epoch: u32, prefix: &[u32]) -> Range<PEP440Version> {
+    let first = PEP440Version {
+        dev: Some(0),
+        ..PEP440Version::from_release(prefix.to_vec()).with_epoch(epoch)
+    };
+    let next = PEP440Version {
+        dev: Some(0),
+        ..first.bump_release(prefix.len() - 1)
+    };
+    Range::between(first, next)
+}
+
+/// Versions matching `~=version`, which is `>=version` and `==prefix.*`
+/// where the prefix drops the last release component of `version`.
+fn compatible_range(
+    version: PEP440Version,
+) -> Result<Range<PEP440Version>, RequirementParseReason> {
+    if version.release.len() < 2 {
+        return Err(RequirementParseReason::CompatibleReleaseTooShort);
+    }
+    let prefix = prefix_range(version.epoch, &version.release[..version.release.len() - 1]);
+    Ok(Range::higher_than(version).intersection(&prefix))
+}
+
+fn compare_to_range(
+    cmp: Compare,
+    version: PEP440Version,
+) -> Result<Range<PEP440Version>, RequirementParseReason> {
+    // PEP 440 only allows local labels for (in)equality
+    if !version.local.is_empty() && !matches!(cmp, Compare::Matching | Compare::Exclusion) {
+        return Err(RequirementParseReason::UnexpectedLocal(cmp));
+    }
+    let range = match cmp {
         Compare::GreaterOrEqual => Range::higher_than(version),
-        Compare::LessOrEqual => {
-            Range::strictly_lower_than(version.clone()).union(&Range::exact(version))
-        }
-        Compare::StrictLess => Range::strictly_lower_than(version),
-        // TODO
-        Compare::Matching => Range::exact(version),
-        Compare::ArbitraryEqual => Range::exact(version),
-        // TODO
-        Compare::Compatible => Range::exact(version),
-        Compare::Exclusion => Range::exact(version).negate(),
-        Compare::StrictGreater => Range::strictly_lower_than(version.clone())
-            .union(&Range::exact(version))
-            .negate(),
-    }
-}
-
-fn parse_specifier(spec: &str) -> Option<Range<PEP440Version>> {
-    SPECIFIER_PATTERN.captures(spec).and_then(|captures| {
-        let cmp = captures
-            .name("compare")
-            .and_then(|cmp| cmp.as_str().parse::<Compare>().ok());
-        let version = captures
-            .name("version")
-            .and_then(|v| v.as_str().parse::<PEP440Version>().ok()).expect(&format!("{} could not be parsed", spec));
-        match (cmp, version) {
-            (Some(cmp), version) => Some(compare_to_range(cmp, version)),
-            _ => None,
+        Compare::LessOrEqual => Range::strictly_lower_than(version.bump_public()),
+        Compare::StrictLess => lower_than(version),
+        Compare::Matching => matching_range(version),
+        // `VersionSpecifier::from_str` handles `===` before parsing the version
+        Compare::ArbitraryEqual => unreachable!("arbitrary equality is not a PEP 440 comparison"),
+        Compare::Compatible => compatible_range(version)?,
+        Compare::Exclusion => matching_range(version).negate(),
+        Compare::StrictGreater => higher_than(version),
+    };
+    Ok(range)
+}
+
+/// Versions matching a prefix specifier such as `==1.4.*` or `!=1.*`.
+///
+/// Only `==` and `!=` accept a prefix, and the prefix may only contain an epoch and a release.
+/// Pre-releases and dev releases of matching versions are part of the range,
+/// so `==1.4.*` contains `1.4.0rc1`; whether they are acceptable is up to the caller.
+/// Local labels of candidates are ignored, so `1.4.2+cpu` matches `==1.4.*` as well.
+fn wildcard_to_range(
+    cmp: Compare,
+    prefix: PEP440Version,
+) -> Result<Range<PEP440Version>, RequirementParseReason> {
+    if prefix.pre.is_some()
+        || prefix.post.is_some()
+        || prefix.dev.is_some()
+        || !prefix.local.is_empty()
+    {
+        return Err(RequirementParseReason::InvalidWildcard);
+    }
+    let range = prefix_range(prefix.epoch, &prefix.release);
+    match cmp {
+        Compare::Matching => Ok(range),
+        Compare::Exclusion => Ok(range.negate()),
+        _ => Err(RequirementParseReason::UnexpectedWildcard(cmp)),
+    }
+}
+
+/// A single version specifier like `>=3.0.2` or `==1.4.*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionSpecifier {
+    compare: Compare,
+    version: String,
+    range: Range<PEP440Version>,
+}
+
+impl VersionSpecifier {
+    /// Versions matching this specifier.
+    pub fn range(&self) -> &Range<PEP440Version> {
+        &self.range
+    }
+
+    /// Whether the version of this specifier is a pre-release or dev release,
+    /// like `>=2.0b1` or `==1.0.dev0`, which PEP 440 takes as asking for pre-releases.
+    /// Prefix matching and arbitrary equality never are.
+    pub fn names_prerelease(&self) -> bool {
+        self.compare != Compare::ArbitraryEqual
+            && self
+                .version
+                .parse::<PEP440Version>()
+                .is_ok_and(|version| version.is_prerelease())
+    }
+}
+
+impl FromStr for VersionSpecifier {
+    type Err = RequirementParseError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let error = |span, reason| RequirementParseError::new(spec, span, reason);
+        let captures = SPECIFIER_PATTERN
+            .captures(spec)
+            .ok_or_else(|| error(0..spec.len(), RequirementParseReason::InvalidSpecifier))?;
+        let (cmp, version) = match (captures.name("compare"), captures.name("version")) {
+            (Some(cmp), Some(version)) => (cmp, version),
+            _ => {
+                return Err(error(
+                    0..spec.len(),
+                    RequirementParseReason::InvalidSpecifier,
+                ))
+            }
+        };
+        let compare = cmp
+            .as_str()
+            .parse::<Compare>()
+            .map_err(|_| error(cmp.range(), RequirementParseReason::InvalidSpecifier))?;
+        let range = if let Compare::ArbitraryEqual = compare {
+            Range::exact(PEP440Version::from_arbitrary(version.as_str()))
+        } else {
+            let (text, wildcard) = match version.as_str().strip_suffix(".*") {
+                Some(prefix) => (prefix, true),
+                None => (version.as_str(), false),
+            };
+            let parsed = text
+                .parse::<PEP440Version>()
+                .map_err(|e| error(version.range(), e.into()))?;
+            let range = if wildcard {
+                wildcard_to_range(compare, parsed)
+            } else {
+                compare_to_range(compare, parsed)
+            };
+            range.map_err(|reason| error(version.range(), reason))?
+        };
+        Ok(VersionSpecifier {
+            compare,
+            version: version.as_str().to_string(),
+            range,
+        })
+    }
+}
+
+impl Display for VersionSpecifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.compare, self.version)
+    }
+}
+
+/// Parse a single version specifier like `>=3.0.2` into a range.
+pub fn parse_specifier(spec: &str) -> Result<Range<PEP440Version>, RequirementParseError> {
+    spec.parse::<VersionSpecifier>().map(|spec| spec.range)
+}
+
+/// Parse comma separated version specifiers like `>=3.6, !=3.0.*` into the range matching all of them,
+/// as found in `Requires-Python`. An empty string matches any version.
+pub fn parse_specifiers(specifiers: &str) -> Result<Range<PEP440Version>, RequirementParseError> {
+    if specifiers.trim().is_empty() {
+        return Ok(Range::any());
+    }
+    let mut range = Range::any();
+    let mut offset = 0;
+    for spec in specifiers.split(',') {
+        let spec_range = parse_specifier(spec).map_err(|e| e.within(specifiers, offset))?;
+        range = range.intersection(&spec_range);
+        offset += spec.len() + 1;
+    }
+    Ok(range)
+}
+
+/// The release of `version` up to `index`, with the component at `index` incremented,
+/// like `2` for `1.2.3` and `1.3` for `1.2.3` when `index` is 1.
+fn next_release(version: &PEP440Version, index: usize) -> PEP440Version {
+    let mut release = version.release.clone();
+    release.resize(index + 1, 0);
+    PEP440Version::from_release(release)
+        .with_epoch(version.epoch)
+        .bump_release(index)
+}
+
+/// Versions matching Poetry's `^version`: up to the next change of the first non-zero
+/// component among major, minor and patch, so `^1.2.3` is `>=1.2.3,<2`,
+/// `^0.2.3` is `>=0.2.3,<0.3` and `^0.0.3` is `>=0.0.3,<0.0.4`.
+/// If they are all zero, the last one given changes, so `^0` is `>=0,<1`
+/// and `^0.0` is `>=0.0,<0.1`.
+fn caret_range(version: PEP440Version) -> Range<PEP440Version> {
+    let significant = &version.release[..version.release.len().min(3)];
+    let index = significant
+        .iter()
+        .position(|&part| part != 0)
+        .unwrap_or(significant.len() - 1);
+    let next = next_release(&version, index);
+    Range::higher_than(version).intersection(&lower_than(next))
+}
+
+/// Versions matching Poetry's `~version`: up to the next minor release,
+/// or the next major release if only the major one is given,
+/// so `~1.2.3` is `>=1.2.3,<1.3` and `~1` is `>=1,<2`.
+fn tilde_range(version: PEP440Version) -> Range<PEP440Version> {
+    let index = if version.release.len() == 1 { 0 } else { 1 };
+    let next = next_release(&version, index);
+    Range::higher_than(version).intersection(&lower_than(next))
+}
+
+/// Parse a version constraint as Poetry writes it in `pyproject.toml` into a range.
+///
+/// Besides PEP 440 specifiers, this accepts the forms of poetry-core:
+/// `^1.2` and `~1.2.3` for caret and tilde requirements,
+/// `1.2.*` or `1.2.x` for wildcards, also after `^`, `==` and `!=`,
+/// `1.2.3` or `=1.2.3` for exact versions
+/// and `*` or an empty constraint for any version.
+/// Constraints separated by commas or whitespace must all match,
+/// while alternatives separated by `||` or `|` are unions like `>=1,<2 || ^3.0`.
+pub fn parse_poetry_constraint(
+    constraint: &str,
+) -> Result<Range<PEP440Version>, RequirementParseError> {
+    if constraint.trim().is_empty() {
+        return Ok(Range::any());
+    }
+    let mut cursor = Cursor::new(constraint);
+    let mut range = cursor.parse_poetry_constraints()?;
+    while cursor.eat('|') {
+        cursor.eat('|');
+        range = range.union(&cursor.parse_poetry_constraints()?);
+    }
+    Ok(range)
+}
+
+impl Cursor<'_> {
+    /// Parse constraints up to the next `|` or the end, which must all match.
+    fn parse_poetry_constraints(&mut self) -> Result<Range<PEP440Version>, RequirementParseError> {
+        let mut range = None;
+        loop {
+            self.eat_whitespace();
+            if self.at_end() || self.peek() == Some('|') {
+                break;
+            }
+            if range.is_some() && self.eat(',') {
+                self.eat_whitespace();
+            }
+            let constraint = self.parse_poetry_single()?;
+            range = Some(range.unwrap_or_else(Range::any).intersection(&constraint));
+        }
+        range.ok_or_else(|| self.expected("a version constraint"))
+    }
+
+    fn parse_poetry_single(&mut self) -> Result<Range<PEP440Version>, RequirementParseError> {
+        let (start, operator) = self.take_while(|c| "^~=!<>".contains(c));
+        self.eat_whitespace();
+        let (version_start, version) =
+            self.take_while(|c| !c.is_whitespace() && c != ',' && c != '|');
+        if version.is_empty() {
+            return Err(self.expected("a version"));
+        }
+        let span = version_start..self.pos;
+        let parse_version = |version: &str| {
+            version
+                .parse::<PEP440Version>()
+                .map_err(|e| self.error(span.clone(), e.into()))
+        };
+        // the release prefix of a wildcard like `1.2.x` or `1.2.*`
+        let prefix = [".x", ".X", ".*"]
+            .iter()
+            .find_map(|suffix| version.strip_suffix(suffix));
+        match operator {
+            "" if version == "*" => Ok(Range::any()),
+            "^" => Ok(caret_range(parse_version(prefix.unwrap_or(version))?)),
+            "~" => Ok(tilde_range(parse_version(version)?)),
+            "" | "=" | "==" | "!=" => {
+                let compare = if operator == "!=" { "!=" } else { "==" };
+                let version = match prefix {
+                    Some(prefix) => format!("{}.*", prefix),
+                    None => version.to_string(),
+                };
+                parse_specifier(&format!("{}{}", compare, version))
+                    .map_err(|e| self.error(span.clone(), e.reason))
+            }
+            _ => parse_specifier(&self.input[start..self.pos])
+                .map_err(|e| e.within(self.input, start)),
+        }
+    }
+}
+
+/// The disjoint intervals making up `range`, in ascending order.
+/// Each interval contains its start but not its end, `None` meaning there is no end.
+pub fn segments(range: &Range<PEP440Version>) -> Vec<(PEP440Version, Option<PEP440Version>)> {
+    // pubgrub does not expose the segments of a range, but they can be found
+    // by alternating between the range and its complement
+    let complement = range.negate();
+    let mut segments = Vec::new();
+    let mut rest = range.clone();
+    while let Some(start) = rest.lowest_version() {
+        let end = complement
+            .intersection(&Range::higher_than(start.clone()))
+            .lowest_version();
+        segments.push((start, end.clone()));
+        match end {
+            Some(end) => rest = rest.intersection(&Range::higher_than(end)),
+            None => break,
         }
+    }
+    segments
+}
+
+/// The version `v` if `bound` is `v.bump_public()` for a version without dev segment,
+/// which is how `<=v` and `>v` are represented.
+fn bumped_public(bound: &PEP440Version) -> Option<PEP440Version> {
+    if bound.is_arbitrary() || !bound.local.is_empty() || bound.dev != Some(0) {
+        return None;
+    }
+    let post = bound.post?;
+    Some(PEP440Version {
+        post: post.checked_sub(1),
+        dev: None,
+        ..bound.clone()
+    })
+}
+
+/// The prefix of `==1.4.*` if the versions from `start` to `end` are exactly the ones it matches.
+fn wildcard_prefix(start: &PEP440Version, end: &PEP440Version) -> Option<String> {
+    if start.is_arbitrary()
+        || start.pre.is_some()
+        || start.post.is_some()
+        || start.dev != Some(0)
+        || !start.local.is_empty()
+    {
+        return None;
+    }
+    (1..=start.release.len())
+        .map(|len| &start.release[..len])
+        .find(|prefix| {
+            Range::between(start.clone(), end.clone()) == prefix_range(start.epoch, prefix)
+        })
+        .map(|prefix| {
+            let prefix = PEP440Version::from_release(prefix.to_vec()).with_epoch(start.epoch);
+            format!("{}.*", prefix)
+        })
+}
+
+/// The version `v` if `bound` is `v.bump_post_releases()`,
+/// which is how `>v` is represented for a version without post or dev segment.
+fn bumped_post_releases(bound: &PEP440Version) -> Option<PEP440Version> {
+    if bound.is_arbitrary() || !bound.above_post_releases {
+        return None;
+    }
+    Some(PEP440Version {
+        above_post_releases: false,
+        ..bound.clone()
     })
 }
 
-pub fn parse_dependency(versions: &str) -> Option<(String, Range<PEP440Version>)> {
-    if let Some(captures) = DEPENDENCY_PATTERN.captures(versions) {
-        // TODO handle extra
-        if captures.name("extra").is_some() {
-            return None;
-        }
-        match (captures.name("name"), captures.name("specs")) {
-            (Some(name), Some(specs)) => {
-                let range = specs
-                    .as_str()
-                    .split(",")
-                    .filter_map(|p| {
-                        let s = parse_specifier(p);
-                        debug_assert!(s.is_some());
-                        s
-                    })
-                    .fold(Range::any(), |acc, r| acc.intersection(&r));
-                return Some((name.as_str().into(), range));
+/// What follows `==` in the specifier matching exactly the versions from `start` to `end`:
+/// a version, a prefix like `1.4.*`, or `=version` for arbitrary equality.
+fn matched(start: &PEP440Version, end: &PEP440Version) -> Option<String> {
+    if end == &start.bump() {
+        let compare = if start.is_arbitrary() { "=" } else { "" };
+        return Some(format!("{}{}", compare, start));
+    }
+    if start.local.is_empty() && end == &start.bump_public() {
+        return Some(start.to_string());
+    }
+    wildcard_prefix(start, end)
+}
+
+/// Lower bound of an interval, `None` if it starts with the lowest version.
+fn lower_bound(start: &PEP440Version) -> Option<String> {
+    if start == &PEP440Version::lowest() {
+        return None;
+    }
+    // `>v` only ends at `v.bump_public()` when `v` is a post release
+    let bumped = bumped_public(start).filter(PEP440Version::is_postrelease);
+    Some(match bumped_post_releases(start).or(bumped) {
+        Some(version) => format!(">{}", version),
+        None => format!(">={}", start),
+    })
+}
+
+fn upper_bound(end: &PEP440Version) -> String {
+    if let Some(version) = bumped_public(end) {
+        return format!("<={}", version);
+    }
+    // `<version` stops before the first dev release of a final release
+    if end.dev == Some(0) && end.pre.is_none() && end.post.is_none() && end.local.is_empty() {
+        return format!(
+            "<{}",
+            PEP440Version {
+                dev: None,
+                ..end.clone()
+            }
+        );
+    }
+    // `<version` leaves out the pre-releases of a final release, which the range still contains
+    if !end.is_arbitrary() && !end.is_prerelease() && !end.is_postrelease() && end.local.is_empty()
+    {
+        return format!("<={},!={}", end, end);
+    }
+    format!("<{}", end)
+}
+
+/// Format an interval with the versions excluded from it as comma separated specifiers.
+fn format_interval(
+    start: &PEP440Version,
+    end: Option<&PEP440Version>,
+    exclusions: &[String],
+) -> String {
+    if exclusions.is_empty() {
+        if let Some(matched) = end.and_then(|end| matched(start, end)) {
+            return format!("=={}", matched);
+        }
+    }
+    let specifiers: Vec<String> = lower_bound(start)
+        .into_iter()
+        .chain(exclusions.iter().map(|excluded| format!("!={}", excluded)))
+        .chain(end.map(upper_bound))
+        .collect();
+    if specifiers.is_empty() {
+        return "*".into();
+    }
+    specifiers.join(",")
+}
+
+/// Format `range` as canonical PEP 440 specifiers like `>=3.0.2,!=3.1.0,<4.0.0`.
+///
+/// Gaps that a single `!=` specifier excludes are written as such,
+/// and disjoint intervals are joined with ` || ` like Poetry does.
+/// Ranges without ` || ` parse back to the same range with [parse_specifiers].
+/// The empty range is `<empty>` and the full range is `*`.
+pub fn format_range(range: &Range<PEP440Version>) -> String {
+    let segments = segments(range);
+    if segments.is_empty() {
+        return "<empty>".into();
+    }
+    let mut intervals = Vec::new();
+    let mut segments = segments.into_iter().peekable();
+    while let Some((start, mut end)) = segments.next() {
+        let mut exclusions = Vec::new();
+        while let (Some(gap), Some((next, next_end))) = (end.as_ref(), segments.peek()) {
+            match matched(gap, next).filter(|excluded| !excluded.starts_with('=')) {
+                Some(excluded) => {
+                    exclusions.push(excluded);
+                    end = next_end.clone();
+                    segments.next();
+                }
+                None => break,
             }
-            (Some(name), None) => return Some((name.as_str().into(), Range::any())),
-            _ => (),
+        }
+        intervals.push(format_interval(&start, end.as_ref(), &exclusions));
+    }
+    intervals.join(" || ")
+}
+
+/// Whether `version` may be picked from `range`.
+///
+/// Arbitrary versions sort before all PEP 440 versions,
+/// so they are part of ranges like `<2.0` or `!=1.0`.
+/// They are only meant to be matched by `===` though,
+/// so they are only allowed when the range does not contain any PEP 440 version.
+pub fn allows(range: &Range<PEP440Version>, version: &PEP440Version) -> bool {
+    if !range.contains(version) {
+        return false;
+    }
+    !version.is_arbitrary()
+        || range.intersection(&Range::higher_than(PEP440Version::lowest_pep440())) == Range::none()
+}
+
+/// Whether a Poetry constraint as accepted by [parse_poetry_constraint] names a pre-release
+/// or dev release, like `>=2.0b1`, `^3.0a1` or `==1.0.dev0`,
+/// which PEP 440 takes as asking for pre-releases.
+/// Wildcards like `1.4.*` never do.
+pub fn names_prerelease(constraint: &str) -> bool {
+    constraint
+        .split(|c: char| c == ',' || c == '|' || c.is_whitespace())
+        .map(|single| single.trim_start_matches(|c| "^~=!<>".contains(c)))
+        .any(|version| {
+            version
+                .parse::<PEP440Version>()
+                .is_ok_and(|version| version.is_prerelease())
+        })
+}
+
+/// Whether the requirements `range` was built from name a pre-release or dev release,
+/// as far as its bounds tell, like `>=2.0b1`, `<2.0rc1` or `==1.0.dev0` do.
+///
+/// The first dev release of a final release like `2.0.dev0` is left out:
+/// it is where `<2.0` and wildcards like `==1.4.*` cut ranges,
+/// so an explicit `>=2.0.dev0` is not told apart from them.
+/// [names_prerelease] decides from the constraint itself where it is at hand.
+pub fn range_names_prerelease(range: &Range<PEP440Version>) -> bool {
+    let cuts_final_release = |bound: &PEP440Version| {
+        bound.dev == Some(0)
+            && bound.pre.is_none()
+            && bound.post.is_none()
+            && bound.local.is_empty()
+    };
+    segments(range)
+        .iter()
+        .flat_map(|(start, end)| std::iter::once(start).chain(end))
+        .any(|bound| bound.is_prerelease() && !cuts_final_release(bound))
+}
+
+/// Whether `range` is a single version pinned with `==` or `===`, without wildcard,
+/// which [PEP 592](https://www.python.org/dev/peps/pep-0592/) allows to pick even if yanked.
+pub fn is_pinned(range: &Range<PEP440Version>) -> bool {
+    match &segments(range)[..] {
+        [(start, Some(end))] => {
+            matched(start, end).is_some() && wildcard_prefix(start, end).is_none()
+        }
+        _ => false,
+    }
+}
+
+/// Which pre-releases may be picked, dev releases counting as pre-releases.
+///
+/// Following PEP 440, pre-releases are excluded unless they are allowed for all packages
+/// or for the package at hand, or the range asked for [names one](range_names_prerelease).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrereleasePolicy {
+    /// Allow pre-releases of every package.
+    pub allow_all: bool,
+    /// Packages whose pre-releases are allowed.
+    pub packages: Vec<String>,
+    /// Pick pre-releases when no final release is in range, like pip does.
+    pub fallback: bool,
+}
+
+impl Default for PrereleasePolicy {
+    fn default() -> Self {
+        PrereleasePolicy {
+            allow_all: false,
+            packages: Vec::new(),
+            fallback: true,
+        }
+    }
+}
+
+impl PrereleasePolicy {
+    /// Allow pre-releases of every package.
+    pub fn allow_all() -> Self {
+        PrereleasePolicy {
+            allow_all: true,
+            ..PrereleasePolicy::default()
+        }
+    }
+
+    /// Also allow pre-releases of `package`.
+    pub fn allow(mut self, package: &str) -> Self {
+        self.packages.push(package.to_string());
+        self
+    }
+
+    /// Whether pre-releases of `package` may be picked even if final releases are.
+    /// Virtual packages for extras follow their base package.
+    pub fn allows_prereleases(&self, package: &str) -> bool {
+        let package = normalize_name(split_extra_package(package).0);
+        self.allow_all || self.packages.iter().any(|p| normalize_name(p) == package)
+    }
+
+    /// The versions of `package` that may be picked from `range`, in the order of `versions`.
+    pub fn candidates(
+        &self,
+        package: &str,
+        range: &Range<PEP440Version>,
+        versions: impl Iterator<Item = PEP440Version>,
+    ) -> Vec<PEP440Version> {
+        let matching: Vec<PEP440Version> = versions.filter(|v| allows(range, v)).collect();
+        if self.allows_prereleases(package) || range_names_prerelease(range) {
+            return matching;
+        }
+        let releases: Vec<PEP440Version> = matching
+            .iter()
+            .filter(|v| !v.is_prerelease())
+            .cloned()
+            .collect();
+        if releases.is_empty() && self.fallback {
+            matching
+        } else {
+            releases
         }
     }
-    None
 }
 
 #[cfg(test)]
 mod test {
-    use crate::ranges::{compare_to_range, parse_dependency, parse_specifier, Compare};
-    use crate::version::PEP440Version;
+    use crate::markers::Environment;
+    use crate::ranges::{
+        allows, compare_to_range, format_range, is_pinned, names_prerelease,
+        parse_poetry_constraint, parse_specifier, parse_specifiers, range_names_prerelease,
+        segments, Compare, PrereleasePolicy, RequirementParseError, RequirementParseReason,
+        VersionSpecifier,
+    };
+    use crate::requirement::Requirement;
+    use crate::version::{PEP440Version, VersionParseError};
+    use proptest::prelude::*;
     use pubgrub::range::Range;
+    use pubgrub::version::Version;
+
+    /// The packages `requirement` adds to the dependency graph for `environment`.
+    fn parse_dependency(
+        requirement: &str,
+        environment: &Environment,
+    ) -> Result<Vec<(String, Range<PEP440Version>)>, RequirementParseError> {
+        let requirement: Requirement = requirement.parse()?;
+        if !requirement.applies_to(environment, &[]) {
+            return Ok(Vec::new());
+        }
+        requirement.dependencies()
+    }
 
     #[test]
     fn test_compare_to_range() {
-        let range = compare_to_range(Compare::GreaterOrEqual, PEP440Version::zero());
+        let range = compare_to_range(Compare::GreaterOrEqual, PEP440Version::lowest()).unwrap();
         assert_eq!(range, Range::any());
     }
 
@@ -117,8 +787,45 @@ mod test {
         let range = parse_specifier("<4.0.0").unwrap();
         assert_eq!(
             range,
-            Range::strictly_lower_than(PEP440Version::new(4, 0, 0))
-        )
+            Range::strictly_lower_than(PEP440Version::new(4, 0, 0).bump_dev())
+        );
+        let rc1 = "4.0.0rc1".parse().unwrap();
+        assert!(!range.contains(&rc1));
+        assert!(parse_specifier("<4.0.0rc2").unwrap().contains(&rc1));
+        assert!(parse_specifier("<4.0.0.post1")
+            .unwrap()
+            .contains(&PEP440Version::new(4, 0, 0)));
+    }
+
+    #[test]
+    fn test_parse_specifier_lt_post() {
+        let parse = |s: &str| s.parse::<PEP440Version>().unwrap();
+        let range = parse_specifier("<1.0.post1").unwrap();
+        assert!(range.contains(&parse("0.9")));
+        assert!(range.contains(&parse("1.0")));
+        assert!(range.contains(&parse("1.0+cpu")));
+        assert!(range.contains(&parse("1.0.post0")));
+        assert!(!range.contains(&parse("1.0rc1")));
+        assert!(!range.contains(&parse("1.0.post1.dev0")));
+        assert!(!range.contains(&parse("1.0.post1")));
+    }
+
+    #[test]
+    fn test_parse_specifier_gt() {
+        let parse = |s: &str| s.parse::<PEP440Version>().unwrap();
+        let range = parse_specifier(">1.7").unwrap();
+        assert!(!range.contains(&parse("1.7")));
+        assert!(!range.contains(&parse("1.7+cpu")));
+        assert!(!range.contains(&parse("1.7.post1")));
+        assert!(!range.contains(&parse("1.7.post1+cpu")));
+        assert!(range.contains(&parse("1.7.0.1")));
+        assert!(range.contains(&parse("1.8.dev0")));
+        let range = parse_specifier(">1.7.post1").unwrap();
+        assert!(!range.contains(&parse("1.7.post1+cpu")));
+        assert!(range.contains(&parse("1.7.post2")));
+        let range = parse_specifier(">1.7rc1").unwrap();
+        assert!(!range.contains(&parse("1.7rc1.post1")));
+        assert!(range.contains(&parse("1.7")));
     }
 
     #[test]
@@ -137,43 +844,643 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_parse_specifiers() {
+        let range = parse_specifiers(">=2.7, !=3.0.*, !=3.1.*").unwrap();
+        assert!(range.contains(&"2.7.18".parse().unwrap()));
+        assert!(!range.contains(&"3.1.4".parse().unwrap()));
+        assert!(range.contains(&"3.6.9".parse().unwrap()));
+        assert_eq!(parse_specifiers(""), Ok(Range::any()));
+        let error = parse_specifiers(">=3.6,<4,>=>1").unwrap_err();
+        assert_eq!(error.input, ">=3.6,<4,>=>1");
+        assert_eq!(error.span, 11..13);
+    }
+
+    #[test]
+    fn test_parse_poetry_constraint() {
+        let specifiers = |specifiers: &str| {
+            specifiers.split(',').fold(Range::any(), |range, s| {
+                range.intersection(&parse_specifier(s).unwrap())
+            })
+        };
+        for (constraint, expected) in &[
+            ("^1.2.3", ">=1.2.3,<2"),
+            ("^1.2", ">=1.2,<2"),
+            ("^1", ">=1,<2"),
+            ("^0.2.3", ">=0.2.3,<0.3"),
+            ("^0.0.3", ">=0.0.3,<0.0.4"),
+            ("^0.0", ">=0.0,<0.1"),
+            ("^0", ">=0,<1"),
+            ("^0.0.0", ">=0.0.0,<0.0.1"),
+            ("^1.2.3b1", ">=1.2.3b1,<2"),
+            ("^1!2.0", ">=1!2.0,<1!3"),
+            ("^0.0.x", ">=0.0,<0.1"),
+            ("^1.2.*", ">=1.2,<2"),
+            ("~1.2.3", ">=1.2.3,<1.3"),
+            ("~1.2", ">=1.2,<1.3"),
+            ("~1", ">=1,<2"),
+            ("~=1.2.3", "~=1.2.3"),
+            ("1.2.*", "==1.2.*"),
+            ("1.2.x", "==1.2.*"),
+            ("1.2.3", "==1.2.3"),
+            ("=1.2.3", "==1.2.3"),
+            ("==1.2.3", "==1.2.3"),
+            ("!=1.2.*", "!=1.2.*"),
+            ("==1.2.x", "==1.2.*"),
+            ("==1.2.*", "==1.2.*"),
+            ("!=1.2.x", "!=1.2.*"),
+            (">=1,<2", ">=1,<2"),
+            (">= 1, < 2", ">=1,<2"),
+            (">=1 <2", ">=1,<2"),
+            ("  >1.0 ", ">1.0"),
+        ] {
+            assert_eq!(
+                parse_poetry_constraint(constraint),
+                Ok(specifiers(expected)),
+                "{}",
+                constraint
+            );
+        }
+        assert_eq!(parse_poetry_constraint("*"), Ok(Range::any()));
+        assert_eq!(parse_poetry_constraint(""), Ok(Range::any()));
+        let expected = specifiers(">=1,<2").union(&specifiers(">=3.0,<4"));
+        assert_eq!(
+            parse_poetry_constraint(">=1,<2 || ^3.0"),
+            Ok(expected.clone())
+        );
+        assert_eq!(parse_poetry_constraint(">=1,<2|^3.0"), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_poetry_constraint_errors() {
+        let invalid = |version: &str| {
+            RequirementParseReason::InvalidVersion(VersionParseError::InvalidVersion {
+                full_version: version.to_string(),
+            })
+        };
+        for (constraint, span, reason) in &[
+            ("^", 1..1, RequirementParseReason::Expected("a version")),
+            (
+                ">=1,,<2",
+                4..5,
+                RequirementParseReason::Expected("a version"),
+            ),
+            (
+                ">=1 ||",
+                6..6,
+                RequirementParseReason::Expected("a version constraint"),
+            ),
+            ("^1.x.2", 1..6, invalid("1.x.2")),
+            ("<=>1", 2..4, invalid(">1")),
+            ("1.2.3+", 0..6, invalid("1.2.3+")),
+        ] {
+            let error = parse_poetry_constraint(constraint).unwrap_err();
+            assert_eq!(&error.input, constraint);
+            assert_eq!(&error.span, span, "{}", constraint);
+            assert_eq!(&error.reason, reason, "{}", constraint);
+        }
+    }
+
+    #[test]
+    fn test_names_prerelease() {
+        for (constraint, names) in &[
+            (">=2.0b1", true),
+            (">2.0b1", true),
+            ("<=1.0rc1", true),
+            ("==1.0rc1", true),
+            ("==1.0.dev3", true),
+            ("==1.0.dev0", true),
+            (">= 1.0.dev0", true),
+            ("!=1.0a1", true),
+            (">=1,<2 || ^3.0a1", true),
+            (">=2", false),
+            ("<=1.0", false),
+            (">1.0.post2", false),
+            ("==1.4.*", false),
+            ("1.4.x", false),
+            ("~=1.4", false),
+            ("!=1.0", false),
+            ("*", false),
+        ] {
+            assert_eq!(names_prerelease(constraint), *names, "{}", constraint);
+        }
+        let spec = |spec: &str| spec.parse::<VersionSpecifier>().unwrap();
+        assert!(spec("==1.0.dev0").names_prerelease());
+        assert!(!spec("==1.0.*").names_prerelease());
+        assert!(!spec("===1.0a1").names_prerelease());
+    }
+
+    #[test]
+    fn test_range_names_prerelease() {
+        for (specifiers, named) in [
+            (">=2.0b1", true),
+            ("<2.0rc1", true),
+            (">1.0rc1", true),
+            ("!=2.0rc1", true),
+            ("==1.0.dev0", true),
+            (">=1.0.post1.dev0", true),
+            (">=1.0", false),
+            ("<2.0", false),
+            ("~=1.4", false),
+            ("==1.0.*", false),
+            ("!=1.0.*", false),
+            // cannot be told apart from the wildcard bounds
+            (">=1.0.dev0", false),
+        ] {
+            let range = parse_specifiers(specifiers).unwrap();
+            assert_eq!(range_names_prerelease(&range), named, "{}", specifiers);
+        }
+    }
+
+    #[test]
+    fn test_is_pinned() {
+        for (constraint, pinned) in &[
+            ("==1.0", true),
+            ("==1.0+cpu", true),
+            ("===foobar", true),
+            (">=0.5,==1.0", true),
+            ("==1.*", false),
+            (">=1.0,<=1.0", true),
+            (">=1.0,<1.1", false),
+            ("!=1.0", false),
+            ("*", false),
+        ] {
+            let range = parse_poetry_constraint(constraint).unwrap();
+            assert_eq!(is_pinned(&range), *pinned, "{}", constraint);
+        }
+    }
+
+    #[test]
+    fn test_prerelease_candidates() {
+        let versions = || {
+            ["3.0.0rc1", "2.1.0", "2.1.0.dev1", "2.0.0"]
+                .iter()
+                .map(|v| v.parse::<PEP440Version>().unwrap())
+        };
+        let candidates = |policy: &PrereleasePolicy, package: &str, constraint: &str| {
+            let range = parse_poetry_constraint(constraint).unwrap();
+            let candidates = policy.candidates(package, &range, versions());
+            candidates.iter().map(|v| v.to_string()).collect::<Vec<_>>()
+        };
+        let default = PrereleasePolicy::default();
+        assert_eq!(candidates(&default, "pkg", ">=2"), vec!["2.1.0", "2.0.0"]);
+        // a range naming a pre-release allows all of them
+        assert_eq!(
+            candidates(&default, "pkg", ">=2.1.0.dev1"),
+            vec!["3.0.0rc1", "2.1.0", "2.1.0.dev1"]
+        );
+        assert_eq!(candidates(&default, "pkg", ">=2.2"), vec!["3.0.0rc1"]);
+        let no_fallback = PrereleasePolicy {
+            fallback: false,
+            ..PrereleasePolicy::default()
+        };
+        assert!(candidates(&no_fallback, "pkg", ">=2.2").is_empty());
+        assert_eq!(
+            candidates(&PrereleasePolicy::allow_all(), "pkg", ">=2.1"),
+            vec!["3.0.0rc1", "2.1.0"]
+        );
+        let per_package = PrereleasePolicy::default().allow("My_Pkg");
+        assert_eq!(
+            candidates(&per_package, "my-pkg[extra]", "<3"),
+            vec!["2.1.0", "2.1.0.dev1", "2.0.0"]
+        );
+        assert_eq!(
+            candidates(&per_package, "other", "<3"),
+            vec!["2.1.0", "2.0.0"]
+        );
+    }
+
+    #[test]
+    fn test_segments() {
+        let range = parse_specifier("<1.0")
+            .unwrap()
+            .union(&parse_specifier(">=2.0").unwrap());
+        assert_eq!(
+            segments(&range),
+            vec![
+                (
+                    PEP440Version::lowest(),
+                    Some(PEP440Version::one().bump_dev())
+                ),
+                (PEP440Version::from_release(vec![2, 0]), None)
+            ]
+        );
+        assert_eq!(segments(&Range::none()), vec![]);
+    }
+
+    #[test]
+    fn test_format_range() {
+        for (specifiers, formatted) in &[
+            (">=3.0.2,<4.0.0", ">=3.0.2,<4.0.0"),
+            ("<4.0.0rc1", "<4.0.0rc1"),
+            (">1.0,<=2.0", ">1.0,<=2.0"),
+            (">1.0.post2", ">1.0.post2"),
+            ("<=1.0a1", "<=1.0a1"),
+            ("==1.7.0", "==1.7.0"),
+            ("==1.7.0+cpu", "==1.7.0+cpu"),
+            ("===foobar", "===foobar"),
+            (">=0", ">=0"),
+            ("<2", "<2"),
+            ("!=1.5", "!=1.5"),
+            (">=1.0,!=1.5,<2.0", ">=1.0,!=1.5,<2.0"),
+            (">=2.7,!=3.0.*,<4", ">=2.7,!=3.0.*,<4"),
+            ("!=1.7.0+cpu", "!=1.7.0+cpu"),
+            ("==1.4.*", "==1.4.*"),
+            ("==1!2.*", "==1!2.*"),
+            ("~=1.4.2", ">=1.4.2,<1.5"),
+            ("<=1.0,!=1.0", "<=1.0,!=1.0"),
+        ] {
+            let range = specifiers.split(',').fold(Range::any(), |range, s| {
+                range.intersection(&parse_specifier(s).unwrap())
+            });
+            assert_eq!(format_range(&range), *formatted);
+        }
+        let range = parse_specifier("<1.0")
+            .unwrap()
+            .union(&parse_specifier(">=2.0").unwrap());
+        assert_eq!(format_range(&range), "<1.0 || >=2.0");
+        let range = parse_specifier("==1.0")
+            .unwrap()
+            .union(&parse_specifier("==2.0").unwrap());
+        assert_eq!(format_range(&range), "==1.0 || ==2.0");
+        assert_eq!(format_range(&Range::any()), "*");
+        assert_eq!(format_range(&Range::none()), "<empty>");
+    }
+
+    #[test]
+    fn test_parse_specifier_local() {
+        let parse = |s: &str| s.parse::<PEP440Version>().unwrap();
+        let range = parse_specifier("==1.7.0").unwrap();
+        assert!(range.contains(&parse("1.7.0")));
+        assert!(range.contains(&parse("1.7.0+cpu")));
+        assert!(range.contains(&parse("1.7.0+cu110")));
+        assert!(!range.contains(&parse("1.7.0.post1")));
+        let range = parse_specifier("==1.7.0+cpu").unwrap();
+        assert!(range.contains(&parse("1.7.0+cpu")));
+        assert!(!range.contains(&parse("1.7.0")));
+        assert!(!range.contains(&parse("1.7.0+cu110")));
+        assert!(!range.contains(&parse("1.7.0+cpu.1")));
+        let range = parse_specifier("!=1.7.0").unwrap();
+        assert!(!range.contains(&parse("1.7.0+cpu")));
+        assert!(range.contains(&parse("1.7.1+cpu")));
+    }
+
+    #[test]
+    fn test_parse_specifier_ordered_local() {
+        let parse = |s: &str| s.parse::<PEP440Version>().unwrap();
+        let range = parse_specifier("<=1.7.0").unwrap();
+        assert!(range.contains(&parse("1.7.0+cpu")));
+        assert!(!range.contains(&parse("1.7.0.post1")));
+        let range = parse_specifier(">1.7.0").unwrap();
+        assert!(!range.contains(&parse("1.7.0+cpu")));
+        assert!(range.contains(&parse("1.7.1")));
+        let range = parse_specifier(">=1.7.0").unwrap();
+        assert!(range.contains(&parse("1.7.0+cpu")));
+    }
+
+    fn assert_range(range: &Range<PEP440Version>, included: &[&str], excluded: &[&str]) {
+        for version in included {
+            assert!(
+                range.contains(&version.parse().unwrap()),
+                "{} in {}",
+                version,
+                range
+            );
+        }
+        for version in excluded {
+            assert!(
+                !range.contains(&version.parse().unwrap()),
+                "{} not in {}",
+                version,
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_specifier_compatible() {
+        // examples from PEP 440
+        let range = parse_specifier("~=2.2").unwrap();
+        assert_eq!(
+            range,
+            Range::between(
+                "2.2".parse::<PEP440Version>().unwrap(),
+                "3.dev0".parse::<PEP440Version>().unwrap()
+            )
+        );
+        assert_range(
+            &range,
+            &["2.2", "2.3", "2.9.9.post1"],
+            &["2.1", "2.2a1", "3.0.dev0", "3.0"],
+        );
+        let range = parse_specifier("~=1.4.5").unwrap();
+        assert_range(
+            &range,
+            &["1.4.5", "1.4.9", "1.4.5+local"],
+            &["1.4.4", "1.5", "1.5a1"],
+        );
+        let range = parse_specifier("~=2.2.post3").unwrap();
+        assert_range(&range, &["2.2.post3", "2.3"], &["2.2", "2.2.post2", "3"]);
+        let range = parse_specifier("~=1.4.5a4").unwrap();
+        assert_range(
+            &range,
+            &["1.4.5a4", "1.4.5", "1.4.8"],
+            &["1.4.5a3", "1.5.dev0"],
+        );
+        let range = parse_specifier("~=2.2.0").unwrap();
+        assert_range(&range, &["2.2.0", "2.2.7"], &["2.3", "2.1.9"]);
+        let range = parse_specifier("~=1.4.5.0").unwrap();
+        assert_range(&range, &["1.4.5", "1.4.5.3"], &["1.4.6", "1.4.4.9"]);
+        let range = parse_specifier("~=1!2.2").unwrap();
+        assert_range(&range, &["1!2.2", "1!2.5"], &["2.2", "1!3.0"]);
+    }
+
+    #[test]
+    fn test_parse_specifier_compatible_invalid() {
+        assert_eq!(
+            parse_specifier("~=1").unwrap_err().reason,
+            RequirementParseReason::CompatibleReleaseTooShort
+        );
+        assert_eq!(
+            parse_specifier("~=1.0+local").unwrap_err().reason,
+            RequirementParseReason::UnexpectedLocal(Compare::Compatible)
+        );
+    }
+
+    #[test]
+    fn test_parse_specifier_wildcard() {
+        let range = parse_specifier("==3.1.*").unwrap();
+        assert_range(
+            &range,
+            &[
+                "3.1",
+                "3.1.0",
+                "3.1.4",
+                "3.1.0rc1",
+                "3.1.5.dev0",
+                "3.1.4+local",
+                "3.1.4.post2",
+            ],
+            &["3.0.9", "3.2.dev0", "3.2", "3.10"],
+        );
+        let range = parse_specifier("!=1.*").unwrap();
+        assert_range(
+            &range,
+            &["0.9", "2.0", "2.0a1"],
+            &["1", "1.0.dev0", "1.9.9", "1.5+cpu"],
+        );
+        let range = parse_specifier("==1.4.0.*").unwrap();
+        assert_range(&range, &["1.4", "1.4.0.1"], &["1.4.1"]);
+        let range = parse_specifier("== 1!2.*").unwrap();
+        assert_range(&range, &["1!2.0"], &["2.0", "1!3.0"]);
+    }
+
+    #[test]
+    fn test_parse_specifier_wildcard_invalid() {
+        assert_eq!(
+            parse_specifier(">=1.4.*").unwrap_err().reason,
+            RequirementParseReason::UnexpectedWildcard(Compare::GreaterOrEqual)
+        );
+        assert_eq!(
+            parse_specifier("~=1.4.*").unwrap_err().reason,
+            RequirementParseReason::UnexpectedWildcard(Compare::Compatible)
+        );
+        assert_eq!(
+            parse_specifier("==1.4a1.*").unwrap_err().reason,
+            RequirementParseReason::InvalidWildcard
+        );
+        assert_eq!(
+            parse_specifier("==1.4+local.*").unwrap_err().reason,
+            RequirementParseReason::InvalidWildcard
+        );
+    }
+
+    #[test]
+    fn test_parsing_django_wildcard() {
+        let range = parse_dependency("Django (==3.1.*)", &Environment::default()).unwrap();
+        assert_eq!(
+            range,
+            vec![("django".into(), parse_specifier("==3.1.*").unwrap())]
+        );
+    }
+
+    #[test]
+    fn test_parse_specifier_arbitrary() {
+        let foobar = PEP440Version::from_arbitrary("foobar");
+        let range = parse_specifier("===foobar").unwrap();
+        assert_eq!(range, Range::exact(foobar.clone()));
+        assert!(allows(&range, &foobar));
+        assert!(allows(&range, &PEP440Version::from_arbitrary("FOOBAR")));
+        assert!(!allows(&range, &PEP440Version::from_arbitrary("foobar2")));
+
+        let range = parse_specifier("===1.0").unwrap();
+        assert!(allows(&range, &PEP440Version::from_arbitrary("1.0")));
+        assert!(!allows(&range, &PEP440Version::from_arbitrary("1.0.0")));
+        assert!(!allows(&range, &PEP440Version::one()));
+    }
+
+    #[test]
+    fn test_allows_arbitrary() {
+        let foobar = PEP440Version::from_arbitrary("foobar");
+        for spec in &["<2.0", "<=2.0", "!=1.0", "!=1.*"] {
+            let range = parse_specifier(spec).unwrap();
+            assert!(range.contains(&foobar));
+            assert!(!allows(&range, &foobar), "{}", spec);
+            assert!(allows(&range, &"0.5".parse().unwrap()), "{}", spec);
+        }
+        assert!(!allows(&Range::any(), &foobar));
+    }
+
     #[test]
     fn test_parsing_chardet() {
         let require = "chardet (<4.0.0,>=3.0.2)";
-        let range = parse_dependency(require).unwrap();
+        let range = parse_dependency(require, &Environment::default()).unwrap();
         assert_eq!(
             range,
-            (
+            vec![(
                 "chardet".into(),
-                Range::between(PEP440Version::new(3, 0, 2), PEP440Version::new(4, 0, 0))
-            )
+                Range::between(
+                    PEP440Version::new(3, 0, 2),
+                    PEP440Version::new(4, 0, 0).bump_dev()
+                )
+            )]
         );
     }
 
     #[test]
     fn test_parsing_idna() {
         let require = "idna (<3.0.0,>=2.5.0)";
-        let range = parse_dependency(require).unwrap();
+        let range = parse_dependency(require, &Environment::default()).unwrap();
         assert_eq!(
             range,
-            (
+            vec![(
                 "idna".into(),
-                Range::between(PEP440Version::new(2, 5, 0), PEP440Version::new(3, 0, 0))
-            )
+                Range::between(
+                    PEP440Version::new(2, 5, 0),
+                    PEP440Version::new(3, 0, 0).bump_dev()
+                )
+            )]
         )
     }
 
     #[test]
     fn test_parsing_pyopenssl() {
         let require = "pyOpenSSL (>=0.14.0) ; extra == 'security'";
-        let range = parse_dependency(require);
-        assert_eq!(range, None)
+        let range = parse_dependency(require, &Environment::default());
+        assert_eq!(range, Ok(vec![]))
+    }
+
+    #[test]
+    fn test_parsing_with_marker() {
+        let python36 = Environment::default().with_python_version("3.6.12");
+        for require in &[
+            "dataclasses ; python_version < '3.7'",
+            "importlib-metadata ; python_version < \"3.8\"",
+        ] {
+            assert_eq!(parse_dependency(require, &python36).unwrap().len(), 1);
+            assert_eq!(
+                parse_dependency(require, &Environment::default()),
+                Ok(vec![])
+            );
+        }
+        let require = "pywin32 (>=1.0) ; sys_platform == 'win32'";
+        assert_eq!(
+            parse_dependency(require, &Environment::default()),
+            Ok(vec![])
+        );
+        let windows = Environment {
+            sys_platform: "win32".into(),
+            ..Environment::default()
+        };
+        assert_eq!(
+            parse_dependency(require, &windows),
+            Ok(vec![("pywin32".into(), parse_specifier(">=1.0").unwrap())])
+        );
+    }
+
+    #[test]
+    fn test_parsing_with_extras() {
+        let require = "cachecontrol[filecache] (>=0.12.4,<0.13)";
+        let range = parse_specifier(">=0.12.4").unwrap();
+        let range = range.intersection(&parse_specifier("<0.13").unwrap());
+        assert_eq!(
+            parse_dependency(require, &Environment::default()),
+            Ok(vec![
+                ("cachecontrol".into(), range.clone()),
+                ("cachecontrol[filecache]".into(), range)
+            ])
+        );
     }
 
     #[test]
     fn test_parsing_without_constrains() {
         let require = "pytz";
-        let range = parse_dependency(require).unwrap();
-        assert_eq!(range, ("pytz".into(), Range::any()));
+        let range = parse_dependency(require, &Environment::default()).unwrap();
+        assert_eq!(range, vec![("pytz".into(), Range::any())]);
+    }
+
+    #[test]
+    fn test_parsing_with_spaces() {
+        let require = "requests (>=2.0, <3)";
+        let range = parse_dependency(require, &Environment::default()).unwrap();
+        assert_eq!(
+            range,
+            vec![(
+                "requests".into(),
+                Range::between(
+                    PEP440Version::from_release(vec![2, 0]),
+                    PEP440Version::from_release(vec![3]).bump_dev()
+                )
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parsing_invalid_version() {
+        let require = "foo (>=1.0,<2.0b1-x)";
+        let error = parse_dependency(require, &Environment::default()).unwrap_err();
+        assert_eq!(error.input, require);
+        assert_eq!(&require[error.span.clone()], "2.0b1-x");
+        assert!(matches!(
+            error.reason,
+            RequirementParseReason::InvalidVersion(VersionParseError::InvalidVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parsing_invalid_specifier() {
+        let require = "foo (>=1.0, =>2)";
+        let error = parse_dependency(require, &Environment::default()).unwrap_err();
+        assert_eq!(&require[error.span.clone()], " =>2");
+        assert_eq!(error.reason, RequirementParseReason::InvalidSpecifier);
+        assert_eq!(
+            error.to_string(),
+            "invalid version specifier at 11..15 in 'foo (>=1.0, =>2)'"
+        );
+    }
+
+    #[test]
+    fn test_parsing_invalid_name() {
+        let require = "-foo (>=1.0)";
+        let error = parse_dependency(require, &Environment::default()).unwrap_err();
+        assert_eq!(error.span, 0..4);
+        assert_eq!(error.reason, RequirementParseReason::InvalidName);
+        assert!(parse_dependency("", &Environment::default()).is_err());
+    }
+
+    fn arbitrary_specifier() -> impl Strategy<Value = String> {
+        let version = (
+            prop::collection::vec(0..3u32, 1..4),
+            prop::option::of(0..2u32),
+            prop::option::of(0..2u32),
+        )
+            .prop_map(|(release, post, dev)| PEP440Version {
+                post,
+                dev,
+                ..PEP440Version::from_release(release)
+            });
+        let prefix = prop::collection::vec(0..3u32, 1..3).prop_map(|release| {
+            let release: Vec<String> = release.iter().map(|part| part.to_string()).collect();
+            format!("{}.*", release.join("."))
+        });
+        prop_oneof![
+            (
+                prop::sample::select(vec!["==", "!=", "<", "<=", ">", ">="]),
+                version
+            )
+                .prop_map(|(compare, version)| format!("{}{}", compare, version)),
+            (prop::sample::select(vec!["==", "!="]), prefix)
+                .prop_map(|(compare, prefix)| format!("{}{}", compare, prefix)),
+        ]
+    }
+
+    fn dependency_range(specifiers: &str) -> Range<PEP440Version> {
+        let dependencies = parse_dependency(&format!("a {}", specifiers), &Environment::default());
+        dependencies.unwrap().remove(0).1
+    }
+
+    proptest! {
+        #[test]
+        fn format_range_round_trip(
+            specifier_sets in prop::collection::vec(
+                prop::collection::vec(arbitrary_specifier(), 0..4),
+                1..3,
+            ),
+        ) {
+            let range = specifier_sets
+                .iter()
+                .map(|specifiers| dependency_range(&specifiers.join(",")))
+                .fold(Range::none(), |range, set| range.union(&set));
+            let formatted = format_range(&range);
+            let parsed = match formatted.as_str() {
+                "<empty>" => Range::none(),
+                "*" => Range::any(),
+                _ => formatted
+                    .split(" || ")
+                    .map(dependency_range)
+                    .fold(Range::none(), |range, interval| range.union(&interval)),
+            };
+            prop_assert_eq!(parsed, range, "{}", formatted);
+        }
     }
 }
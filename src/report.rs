@@ -0,0 +1,496 @@
+use crate::ranges::{format_range, range_names_prerelease, segments, PrereleasePolicy};
+use crate::version::PEP440Version;
+use pubgrub::range::Range;
+use pubgrub::report::{DerivationTree, Derived, External, Reporter};
+use pubgrub::term::Term;
+use pubgrub::type_aliases::Map;
+use std::ops::Deref;
+
+type Terms = Map<String, Term<PEP440Version>>;
+
+/// Explains resolution failures like Poetry does, for example
+/// `Because a (1.0) depends on c (<2) and b depends on c (>=2), a (1.0) is incompatible with b.`
+///
+/// Ranges are written as PEP 440 specifiers, the root project is named without its version
+/// and its failure reads as "version solving failed".
+/// Hints on how to fix the conflict follow the explanation.
+///
+/// The explanation is built like the one of [pubgrub::report::DefaultStringReporter],
+/// with explanations used more than once numbered like `(1)`.
+pub struct PoetryReporter {
+    root: Option<String>,
+    /// Pre-releases the resolution was allowed to pick.
+    prereleases: PrereleasePolicy,
+    /// Ranges of packages without any available version.
+    unavailable: Vec<(String, Range<PEP440Version>)>,
+    /// Number of explanations already with a line reference.
+    ref_count: usize,
+    /// Line references of shared incompatibilities, by incompatibility id.
+    shared_with_ref: Map<usize, usize>,
+    lines: Vec<String>,
+}
+
+/// Explains a failure of a resolution using the [default pre-release policy](PrereleasePolicy::default).
+impl Reporter<String, PEP440Version> for PoetryReporter {
+    type Output = String;
+
+    fn report(derivation_tree: &DerivationTree<String, PEP440Version>) -> Self::Output {
+        PoetryReporter::report_with_prereleases(derivation_tree, &PrereleasePolicy::default())
+    }
+}
+
+impl PoetryReporter {
+    /// Explains a failure of a resolution that picked pre-releases according to `prereleases`.
+    pub fn report_with_prereleases(
+        derivation_tree: &DerivationTree<String, PEP440Version>,
+        prereleases: &PrereleasePolicy,
+    ) -> String {
+        let mut externals = Vec::new();
+        collect_externals(derivation_tree, &mut externals);
+        let unavailable = externals
+            .iter()
+            .filter_map(|external| match external {
+                External::NoVersions(package, range) => Some((package.clone(), range.clone())),
+                _ => None,
+            })
+            .collect();
+        let mut reporter = PoetryReporter {
+            root: root_package(derivation_tree),
+            prereleases: prereleases.clone(),
+            unavailable,
+            ref_count: 0,
+            shared_with_ref: Map::default(),
+            lines: Vec::new(),
+        };
+        // the explanation is much shorter without the missing versions,
+        // which are mentioned with the dependencies they are missing for
+        let mut collapsed = derivation_tree.clone();
+        collapsed.collapse_no_versions();
+        match &collapsed {
+            DerivationTree::External(external) => {
+                reporter.lines.push(format!(
+                    "Because {}, version solving failed.",
+                    reporter.external(external)
+                ));
+            }
+            DerivationTree::Derived(derived) => reporter.build_recursive(derived),
+        }
+        let mut report = reporter.lines.join("\n");
+        let hints = reporter.hints(&externals);
+        if !hints.is_empty() {
+            report.push_str("\n\nHints:");
+            for hint in hints {
+                report.push_str("\n  * ");
+                report.push_str(&hint);
+            }
+        }
+        report
+    }
+
+    fn build_recursive(&mut self, derived: &Derived<String, PEP440Version>) {
+        self.build_recursive_helper(derived);
+        if let Some(id) = derived.shared_id {
+            if !self.shared_with_ref.contains_key(&id) {
+                self.add_line_ref();
+                self.shared_with_ref.insert(id, self.ref_count);
+            }
+        }
+    }
+
+    fn build_recursive_helper(&mut self, current: &Derived<String, PEP440Version>) {
+        match (current.cause1.deref(), current.cause2.deref()) {
+            (DerivationTree::External(external1), DerivationTree::External(external2)) => {
+                let line = format!(
+                    "Because {} and {}, {}.",
+                    self.external(external1),
+                    self.external(external2),
+                    self.terms(&current.terms)
+                );
+                self.lines.push(line);
+            }
+            (DerivationTree::Derived(derived), DerivationTree::External(external))
+            | (DerivationTree::External(external), DerivationTree::Derived(derived)) => {
+                self.report_one_each(derived, external, &current.terms);
+            }
+            (DerivationTree::Derived(derived1), DerivationTree::Derived(derived2)) => {
+                match (
+                    self.line_ref_of(derived1.shared_id),
+                    self.line_ref_of(derived2.shared_id),
+                ) {
+                    (Some(ref1), Some(ref2)) => {
+                        let line = format!(
+                            "Because {} ({}) and {} ({}), {}.",
+                            self.terms(&derived1.terms),
+                            ref1,
+                            self.terms(&derived2.terms),
+                            ref2,
+                            self.terms(&current.terms)
+                        );
+                        self.lines.push(line);
+                    }
+                    (Some(ref1), None) => {
+                        self.build_recursive(derived2);
+                        self.and_explain_ref(ref1, derived1, &current.terms);
+                    }
+                    (None, Some(ref2)) => {
+                        self.build_recursive(derived1);
+                        self.and_explain_ref(ref2, derived2, &current.terms);
+                    }
+                    (None, None) => {
+                        self.build_recursive(derived1);
+                        if derived1.shared_id.is_some() {
+                            self.lines.push("".into());
+                            self.build_recursive(current);
+                        } else {
+                            self.add_line_ref();
+                            let ref1 = self.ref_count;
+                            self.lines.push("".into());
+                            self.build_recursive(derived2);
+                            self.and_explain_ref(ref1, derived1, &current.terms);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Report a derived and an external incompatibility,
+    /// depending on whether the derived one has already been explained.
+    fn report_one_each(
+        &mut self,
+        derived: &Derived<String, PEP440Version>,
+        external: &External<String, PEP440Version>,
+        current_terms: &Terms,
+    ) {
+        if let Some(ref_id) = self.line_ref_of(derived.shared_id) {
+            let line = format!(
+                "Because {} ({}) and {}, {}.",
+                self.terms(&derived.terms),
+                ref_id,
+                self.external(external),
+                self.terms(current_terms)
+            );
+            self.lines.push(line);
+            return;
+        }
+        match (derived.cause1.deref(), derived.cause2.deref()) {
+            // chain the external causes if the derived one has one itself
+            (DerivationTree::Derived(prior_derived), DerivationTree::External(prior_external))
+            | (DerivationTree::External(prior_external), DerivationTree::Derived(prior_derived)) => {
+                self.build_recursive(prior_derived);
+                let line = format!(
+                    "And because {} and {}, {}.",
+                    self.external(prior_external),
+                    self.external(external),
+                    self.terms(current_terms)
+                );
+                self.lines.push(line);
+            }
+            _ => {
+                self.build_recursive(derived);
+                let line = format!(
+                    "And because {}, {}.",
+                    self.external(external),
+                    self.terms(current_terms)
+                );
+                self.lines.push(line);
+            }
+        }
+    }
+
+    fn and_explain_ref(
+        &mut self,
+        ref_id: usize,
+        derived: &Derived<String, PEP440Version>,
+        current_terms: &Terms,
+    ) {
+        let line = format!(
+            "And because {} ({}), {}.",
+            self.terms(&derived.terms),
+            ref_id,
+            self.terms(current_terms)
+        );
+        self.lines.push(line);
+    }
+
+    fn add_line_ref(&mut self) {
+        self.ref_count += 1;
+        let ref_count = self.ref_count;
+        if let Some(line) = self.lines.last_mut() {
+            *line = format!("({}) {}", ref_count, line);
+        }
+    }
+
+    fn line_ref_of(&self, shared_id: Option<usize>) -> Option<usize> {
+        shared_id.and_then(|id| self.shared_with_ref.get(&id).cloned())
+    }
+
+    fn is_root(&self, package: &str) -> bool {
+        self.root.as_deref() == Some(package)
+    }
+
+    /// A package with its range like `requests (>=2.18,<3)` or `requests (2.25.0)`.
+    fn package(&self, package: &str, range: &Range<PEP440Version>) -> String {
+        if self.is_root(package) || range == &Range::any() {
+            return package.to_string();
+        }
+        let range = format_range(range);
+        match range.strip_prefix("==") {
+            Some(version) if !version.starts_with('=') => format!("{} ({})", package, version),
+            _ => format!("{} ({})", package, range),
+        }
+    }
+
+    /// Whether no version at all is available in `range` of `package`.
+    /// Whether pre-releases of `package` were left out of `range` even without a final release in it.
+    fn excludes_prereleases(&self, package: &str, range: &Range<PEP440Version>) -> bool {
+        !self.prereleases.fallback
+            && !self.prereleases.allows_prereleases(package)
+            && !range_names_prerelease(range)
+    }
+
+    fn is_unavailable(&self, package: &str, range: &Range<PEP440Version>) -> bool {
+        self.unavailable
+            .iter()
+            .any(|(p, r)| p == package && &range.intersection(r) == range)
+    }
+
+    fn external(&self, external: &External<String, PEP440Version>) -> String {
+        match external {
+            External::NotRoot(package, version) => {
+                format!("we are solving dependencies of {} ({})", package, version)
+            }
+            External::NoVersions(package, range) if range == &Range::any() => {
+                format!("{} doesn't match any versions", package)
+            }
+            External::NoVersions(package, range) => {
+                format!("no versions of {} match {}", package, format_range(range))
+            }
+            External::UnavailableDependencies(package, range) => format!(
+                "the dependencies of {} could not be retrieved",
+                self.package(package, range)
+            ),
+            External::FromDependencyOf(package, range, dependency, dependency_range) => {
+                let unavailable = if self.is_unavailable(dependency, dependency_range) {
+                    " which doesn't match any versions"
+                } else {
+                    ""
+                };
+                format!(
+                    "{} depends on {}{}",
+                    self.package(package, range),
+                    self.package(dependency, dependency_range),
+                    unavailable
+                )
+            }
+        }
+    }
+
+    /// Conclusion of an incompatibility with the given terms.
+    fn terms(&self, terms: &Terms) -> String {
+        let mut terms: Vec<_> = terms.iter().collect();
+        // root first, then positive terms, for sentences in a stable order
+        terms.sort_by_key(|(package, term)| {
+            (
+                !self.is_root(package),
+                matches!(term, Term::Negative(_)),
+                package.to_string(),
+            )
+        });
+        match terms[..] {
+            [] => "version solving failed".into(),
+            [(package, Term::Positive(_))] if self.is_root(package) => {
+                "version solving failed".into()
+            }
+            [(package, Term::Positive(range))] => {
+                format!("{} is forbidden", self.package(package, range))
+            }
+            [(package, Term::Negative(range))] => {
+                format!("{} is required", self.package(package, range))
+            }
+            [(p1, Term::Positive(r1)), (p2, Term::Negative(r2))] => {
+                format!("{} requires {}", self.package(p1, r1), self.package(p2, r2))
+            }
+            [(p1, Term::Positive(r1)), (p2, Term::Positive(r2))] => format!(
+                "{} is incompatible with {}",
+                self.package(p1, r1),
+                self.package(p2, r2)
+            ),
+            ref terms => {
+                let terms: Vec<String> = terms
+                    .iter()
+                    .map(|(package, term)| match term {
+                        Term::Positive(range) => self.package(package, range),
+                        Term::Negative(range) => format!("not {}", self.package(package, range)),
+                    })
+                    .collect();
+                format!("{} are incompatible", terms.join(", "))
+            }
+        }
+    }
+
+    /// Suggestions for the constraints involved in the conflict, without duplicates.
+    fn hints(&self, externals: &[&External<String, PEP440Version>]) -> Vec<String> {
+        let mut hints: Vec<String> = Vec::new();
+        let mut add = |hint: String| {
+            if !hints.contains(&hint) {
+                hints.push(hint);
+            }
+        };
+        for external in externals {
+            if let External::FromDependencyOf(package, _, dependency, range) = external {
+                if self.is_root(package) && range != &Range::any() {
+                    add(format!(
+                        "Try relaxing the constraint on {}.",
+                        self.package(dependency, range)
+                    ));
+                }
+                if self.is_unavailable(dependency, range)
+                    && self.excludes_prereleases(dependency, range)
+                    && may_match_prerelease(range)
+                {
+                    add(format!(
+                        "A newer version of {} may be available with allow-prereleases.",
+                        dependency
+                    ));
+                }
+            }
+        }
+        hints
+    }
+}
+
+/// The package solving failed for, which is the only term of the last incompatibility.
+fn root_package(derivation_tree: &DerivationTree<String, PEP440Version>) -> Option<String> {
+    match derivation_tree {
+        DerivationTree::External(External::NotRoot(package, _))
+        | DerivationTree::External(External::FromDependencyOf(package, _, _, _)) => {
+            Some(package.clone())
+        }
+        DerivationTree::External(_) => None,
+        DerivationTree::Derived(derived) => match derived.terms.iter().collect::<Vec<_>>()[..] {
+            [(package, Term::Positive(_))] => Some(package.clone()),
+            _ => None,
+        },
+    }
+}
+
+fn collect_externals<'a>(
+    derivation_tree: &'a DerivationTree<String, PEP440Version>,
+    externals: &mut Vec<&'a External<String, PEP440Version>>,
+) {
+    match derivation_tree {
+        DerivationTree::External(external) => externals.push(external),
+        DerivationTree::Derived(derived) => {
+            collect_externals(&derived.cause1, externals);
+            collect_externals(&derived.cause2, externals);
+        }
+    }
+}
+
+/// Whether only a pre-release could match a range without any available version:
+/// the range asks for a pre-release or for anything newer than some version.
+fn may_match_prerelease(range: &Range<PEP440Version>) -> bool {
+    segments(range)
+        .iter()
+        .any(|(start, end)| start.pre.is_some() || end.is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ranges::{parse_specifier, PrereleasePolicy};
+    use crate::report::PoetryReporter;
+    use crate::version::PEP440Version;
+    use pubgrub::error::PubGrubError;
+    use pubgrub::range::Range;
+    use pubgrub::report::Reporter;
+    use pubgrub::solver::{resolve, OfflineDependencyProvider};
+
+    fn report(provider: &OfflineDependencyProvider<String, PEP440Version>) -> String {
+        match resolve(provider, "myapp".into(), PEP440Version::one()) {
+            Err(PubGrubError::NoSolution(tree)) => PoetryReporter::report(&tree),
+            result => panic!("expected no solution, got {:?}", result),
+        }
+    }
+
+    fn add(
+        provider: &mut OfflineDependencyProvider<String, PEP440Version>,
+        package: &str,
+        version: &str,
+        dependencies: &[(&str, &str)],
+    ) {
+        let dependencies: Vec<(String, Range<PEP440Version>)> = dependencies
+            .iter()
+            .map(|(name, spec)| {
+                let range = match *spec {
+                    "*" => Range::any(),
+                    spec => parse_specifier(spec).unwrap(),
+                };
+                (name.to_string(), range)
+            })
+            .collect();
+        provider.add_dependencies(
+            package.into(),
+            version.parse::<PEP440Version>().unwrap(),
+            dependencies,
+        );
+    }
+
+    #[test]
+    fn report_conflicting_dependencies() {
+        let mut provider = OfflineDependencyProvider::new();
+        add(&mut provider, "myapp", "1.0", &[("a", "*"), ("b", "==1.0")]);
+        add(&mut provider, "a", "1.0", &[("c", "<2")]);
+        add(&mut provider, "b", "1.0", &[("c", ">=2")]);
+        add(&mut provider, "c", "1.0", &[]);
+        add(&mut provider, "c", "2.0", &[]);
+        assert_eq!(
+            report(&provider),
+            "Because b (1.0) depends on c (>=2) and a (1.0) depends on c (<2), \
+            a (1.0) is incompatible with b (1.0).\n\
+            And because myapp depends on b (1.0) and myapp depends on a, \
+            version solving failed.\n\
+            \n\
+            Hints:\n  \
+            * Try relaxing the constraint on b (1.0)."
+        );
+    }
+
+    #[test]
+    fn report_missing_versions() {
+        let mut provider = OfflineDependencyProvider::new();
+        add(&mut provider, "myapp", "1.0", &[("requests", ">=3")]);
+        add(&mut provider, "requests", "2.25.0", &[]);
+        // pre-releases are picked when no final release is in range, so they cannot help
+        assert_eq!(
+            report(&provider),
+            "Because myapp depends on requests (>=3) which doesn't match any versions, \
+            version solving failed.\n\
+            \n\
+            Hints:\n  \
+            * Try relaxing the constraint on requests (>=3)."
+        );
+        let tree = match resolve(&provider, "myapp".into(), PEP440Version::one()) {
+            Err(PubGrubError::NoSolution(tree)) => tree,
+            result => panic!("expected no solution, got {:?}", result),
+        };
+        let without_fallback = PrereleasePolicy {
+            fallback: false,
+            ..PrereleasePolicy::default()
+        };
+        assert_eq!(
+            PoetryReporter::report_with_prereleases(&tree, &without_fallback),
+            "Because myapp depends on requests (>=3) which doesn't match any versions, \
+            version solving failed.\n\
+            \n\
+            Hints:\n  \
+            * Try relaxing the constraint on requests (>=3).\n  \
+            * A newer version of requests may be available with allow-prereleases."
+        );
+        assert!(!PoetryReporter::report_with_prereleases(
+            &tree,
+            &without_fallback.clone().allow("requests")
+        )
+        .contains("allow-prereleases"));
+    }
+}
@@ -0,0 +1,658 @@
+use crate::markers::{Environment, MarkerTree};
+use crate::ranges::{RequirementParseError, RequirementParseReason, VersionSpecifier};
+use crate::version::PEP440Version;
+use lazy_static::lazy_static;
+use pubgrub::range::Range;
+use regex::Regex;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+lazy_static! {
+    // from PEP 508, also used for extras
+    pub static ref NAME_PATTERN: Regex = Regex::new(r"(?i)^([A-Z0-9]|[A-Z0-9][A-Z0-9._-]*[A-Z0-9])$").unwrap();
+    static ref NAME_SEPARATORS: Regex = Regex::new(r"[-_.]+").unwrap();
+}
+
+/// Normalized form of a package or extra name as defined in
+/// [PEP 503](https://www.python.org/dev/peps/pep-0503/#normalized-names),
+/// so `Foo.Bar_baz` and `foo-bar-baz` are the same.
+pub fn normalize_name(name: &str) -> String {
+    NAME_SEPARATORS.replace_all(name, "-").to_lowercase()
+}
+
+/// Name of the virtual package standing for `package` installed with `extra`, like `requests[security]`.
+/// Both names are normalized, so every spelling of them yields the same package.
+///
+/// It depends on exactly the same version of `package`
+/// and on the requirements of `package` that only apply with the extra.
+pub fn extra_package(package: &str, extra: &str) -> String {
+    format!("{}[{}]", normalize_name(package), normalize_name(extra))
+}
+
+/// Split a package name into the base package and the extra of a [virtual package](extra_package).
+pub fn split_extra_package(package: &str) -> (&str, Option<&str>) {
+    match package.strip_suffix(']').and_then(|p| p.split_once('[')) {
+        Some((base, extra)) => (base, Some(extra)),
+        None => (package, None),
+    }
+}
+
+/// A [PEP 508](https://www.python.org/dev/peps/pep-0508/) dependency specification,
+/// like `requests[security] (>=2.8.1,<3) ; python_version < "3.8"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub name: String,
+    pub extras: Vec<String>,
+    /// Version specifiers, all of which must match.
+    /// Always empty for URL requirements.
+    pub version_spec: Vec<VersionSpecifier>,
+    /// Direct reference given with `name @ url`.
+    pub url: Option<String>,
+    /// Environment marker following `;`, deciding whether the requirement applies.
+    pub marker: Option<MarkerTree>,
+}
+
+impl Requirement {
+    /// Versions allowed by all version specifiers.
+    pub fn range(&self) -> Range<PEP440Version> {
+        self.version_spec
+            .iter()
+            .fold(Range::any(), |range, spec| range.intersection(spec.range()))
+    }
+
+    /// Whether one of the version specifiers [names a pre-release](VersionSpecifier::names_prerelease).
+    pub fn names_prerelease(&self) -> bool {
+        self.version_spec
+            .iter()
+            .any(VersionSpecifier::names_prerelease)
+    }
+
+    /// Whether the requirement applies to `environment` when the given extras are requested.
+    pub fn applies_to(&self, environment: &Environment, extras: &[String]) -> bool {
+        self.marker
+            .as_ref()
+            .is_none_or(|marker| marker.evaluate(environment, extras))
+    }
+
+    /// Packages to add to the dependency graph with their range:
+    /// the required package and a [virtual package](extra_package) for each requested extra.
+    /// Package names are [normalized](normalize_name).
+    ///
+    /// Fails for a direct reference like `name @ url`, which cannot be resolved from an index.
+    pub fn dependencies(
+        &self,
+    ) -> Result<Vec<(String, Range<PEP440Version>)>, RequirementParseError> {
+        if let Some(url) = &self.url {
+            let input = self.to_string();
+            let start = input.find(" @ ").map_or(0, |at| at + 3);
+            return Err(RequirementParseError::new(
+                &input,
+                start..start + url.len(),
+                RequirementParseReason::DirectReference,
+            ));
+        }
+        let range = self.range();
+        Ok(std::iter::once(normalize_name(&self.name))
+            .chain(self.extras.iter().map(|e| extra_package(&self.name, e)))
+            .map(|package| (package, range.clone()))
+            .collect())
+    }
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.extras.is_empty() {
+            write!(f, "[{}]", self.extras.join(","))?;
+        }
+        let specs: Vec<String> = self.version_spec.iter().map(|s| s.to_string()).collect();
+        write!(f, "{}", specs.join(","))?;
+        if let Some(url) = &self.url {
+            write!(f, " @ {}", url)?;
+        }
+        if let Some(marker) = &self.marker {
+            // a marker must be separated from the url by whitespace
+            if self.url.is_some() {
+                write!(f, " ")?;
+            }
+            write!(f, "; {}", marker)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = RequirementParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Cursor::new(s).parse_requirement()
+    }
+}
+
+/// Position in the requirement being parsed.
+pub(crate) struct Cursor<'a> {
+    pub(crate) input: &'a str,
+    pub(crate) pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn new(input: &'a str) -> Self {
+        Cursor { input, pos: 0 }
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    pub(crate) fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    pub(crate) fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consume characters while `f` holds, returning their start and the consumed text.
+    pub(crate) fn take_while(&mut self, f: impl Fn(char) -> bool) -> (usize, &'a str) {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !f(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        (start, &self.input[start..self.pos])
+    }
+
+    /// Skip whitespace, returning whether there was any.
+    pub(crate) fn eat_whitespace(&mut self) -> bool {
+        !self.take_while(char::is_whitespace).1.is_empty()
+    }
+
+    pub(crate) fn error(
+        &self,
+        span: std::ops::Range<usize>,
+        reason: RequirementParseReason,
+    ) -> RequirementParseError {
+        RequirementParseError::new(self.input, span, reason)
+    }
+
+    /// Error about the character at the current position, or the end of the input.
+    pub(crate) fn expected(&self, what: &'static str) -> RequirementParseError {
+        let end = self.pos + self.peek().map_or(0, char::len_utf8);
+        self.error(self.pos..end, RequirementParseReason::Expected(what))
+    }
+
+    fn parse_identifier(
+        &mut self,
+        reason: RequirementParseReason,
+    ) -> Result<String, RequirementParseError> {
+        let (start, identifier) =
+            self.take_while(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+        if NAME_PATTERN.is_match(identifier) {
+            Ok(identifier.to_string())
+        } else if identifier.is_empty() {
+            let end = start + self.peek().map_or(0, char::len_utf8);
+            Err(self.error(start..end, reason))
+        } else {
+            Err(self.error(start..self.pos, reason))
+        }
+    }
+
+    fn parse_requirement(&mut self) -> Result<Requirement, RequirementParseError> {
+        self.eat_whitespace();
+        let name = self.parse_identifier(RequirementParseReason::InvalidName)?;
+        self.eat_whitespace();
+        let extras = if self.eat('[') {
+            self.parse_extras()?
+        } else {
+            Vec::new()
+        };
+        self.eat_whitespace();
+        let mut version_spec = Vec::new();
+        let mut url = None;
+        if self.eat('@') {
+            self.eat_whitespace();
+            let (_, text) = self.take_while(|c| !c.is_whitespace());
+            if text.is_empty() {
+                return Err(self.expected("a URL after '@'"));
+            }
+            // the url takes everything up to the next whitespace,
+            // so a marker has to be separated from it
+            url = Some(text.to_string());
+        } else if self.eat('(') {
+            version_spec = self.parse_version_many()?;
+            if version_spec.is_empty() {
+                return Err(self.expected("a version specifier"));
+            }
+            self.eat_whitespace();
+            if !self.eat(')') {
+                return Err(self.expected("',' or ')'"));
+            }
+        } else {
+            version_spec = self.parse_version_many()?;
+        }
+        self.eat_whitespace();
+        let marker = if self.eat(';') {
+            let marker = self.parse_marker()?;
+            self.eat_whitespace();
+            if !self.at_end() {
+                return Err(self.expected("'and', 'or' or the end of the marker"));
+            }
+            Some(marker)
+        } else {
+            None
+        };
+        if !self.at_end() {
+            return Err(self.expected("a version specifier, ';' or the end of the requirement"));
+        }
+        Ok(Requirement {
+            name,
+            extras,
+            version_spec,
+            url,
+            marker,
+        })
+    }
+
+    /// Extras after the opening `[`, up to and including the closing `]`.
+    fn parse_extras(&mut self) -> Result<Vec<String>, RequirementParseError> {
+        let mut extras = Vec::new();
+        self.eat_whitespace();
+        if self.eat(']') {
+            return Ok(extras);
+        }
+        loop {
+            self.eat_whitespace();
+            extras.push(self.parse_identifier(RequirementParseReason::InvalidExtra)?);
+            self.eat_whitespace();
+            if self.eat(']') {
+                return Ok(extras);
+            }
+            if !self.eat(',') {
+                return Err(self.expected("',' or ']'"));
+            }
+        }
+    }
+
+    /// Comma separated version specifiers, possibly none.
+    fn parse_version_many(&mut self) -> Result<Vec<VersionSpecifier>, RequirementParseError> {
+        let mut specs = Vec::new();
+        loop {
+            // errors in specifiers after a comma span from the comma on
+            let start = self.pos;
+            self.eat_whitespace();
+            let start = if specs.is_empty() { self.pos } else { start };
+            let (_, operator) = self.take_while(|c| "<>=!~".contains(c));
+            if operator.is_empty() {
+                if specs.is_empty() {
+                    return Ok(specs);
+                }
+                return Err(self.expected("a version specifier"));
+            }
+            self.eat_whitespace();
+            self.take_while(|c| c.is_ascii_alphanumeric() || "-_.*+!".contains(c));
+            let spec = &self.input[start..self.pos];
+            let spec = spec
+                .parse::<VersionSpecifier>()
+                .map_err(|e| e.within(self.input, start))?;
+            specs.push(spec);
+            self.eat_whitespace();
+            if !self.eat(',') {
+                return Ok(specs);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markers::MarkerTree;
+    use crate::ranges::{RequirementParseReason, VersionSpecifier};
+    use crate::requirement::{extra_package, normalize_name, split_extra_package, Requirement};
+    use proptest::prelude::*;
+    use pubgrub::range::Range;
+
+    fn specs(specs: &[&str]) -> Vec<VersionSpecifier> {
+        specs.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    fn requirement(
+        name: &str,
+        extras: &[&str],
+        version_spec: &[&str],
+        url: Option<&str>,
+        marker: Option<&str>,
+    ) -> Requirement {
+        Requirement {
+            name: name.into(),
+            extras: extras.iter().map(|e| e.to_string()).collect(),
+            version_spec: specs(version_spec),
+            url: url.map(String::from),
+            marker: marker.map(|m| m.parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn parse_valid_requirements() {
+        // test vectors from the `packaging` python package
+        let vectors = vec![
+            ("name", requirement("name", &[], &[], None, None)),
+            (
+                "foo-bar.quux_baz",
+                requirement("foo-bar.quux_baz", &[], &[], None, None),
+            ),
+            ("name>=3", requirement("name", &[], &[">=3"], None, None)),
+            ("name >= 3", requirement("name", &[], &[">=3"], None, None)),
+            (
+                "name>=3,<2",
+                requirement("name", &[], &[">=3", "<2"], None, None),
+            ),
+            ("name (>=3)", requirement("name", &[], &[">=3"], None, None)),
+            (
+                "name (>=3, <2)",
+                requirement("name", &[], &[">=3", "<2"], None, None),
+            ),
+            (
+                "name===arbitrarystring",
+                requirement("name", &[], &["===arbitrarystring"], None, None),
+            ),
+            ("name[]", requirement("name", &[], &[], None, None)),
+            ("name[bar]", requirement("name", &["bar"], &[], None, None)),
+            (
+                "name[bar,baz]>=1.0",
+                requirement("name", &["bar", "baz"], &[">=1.0"], None, None),
+            ),
+            (
+                "name [fred,bar] @ http://foo.com ; python_version=='2.7'",
+                requirement(
+                    "name",
+                    &["fred", "bar"],
+                    &[],
+                    Some("http://foo.com"),
+                    Some("python_version=='2.7'"),
+                ),
+            ),
+            (
+                "name[quux, strange];python_version<'2.7' and platform_version=='2'",
+                requirement(
+                    "name",
+                    &["quux", "strange"],
+                    &[],
+                    None,
+                    Some("python_version<'2.7' and platform_version=='2'"),
+                ),
+            ),
+            (
+                "name; os_name=='a' or os_name=='b'",
+                requirement("name", &[], &[], None, Some("os_name=='a' or os_name=='b'")),
+            ),
+            (
+                "name @ https://github.com/pypa/packaging/archive/refs/heads/main.zip",
+                requirement(
+                    "name",
+                    &[],
+                    &[],
+                    Some("https://github.com/pypa/packaging/archive/refs/heads/main.zip"),
+                    None,
+                ),
+            ),
+            (
+                "name@http://foo.com",
+                requirement("name", &[], &[], Some("http://foo.com"), None),
+            ),
+            (
+                "name @ file:///absolute/path",
+                requirement("name", &[], &[], Some("file:///absolute/path"), None),
+            ),
+            (
+                "pyOpenSSL (>=0.14) ; extra == 'security'",
+                requirement(
+                    "pyOpenSSL",
+                    &[],
+                    &[">=0.14"],
+                    None,
+                    Some("extra == 'security'"),
+                ),
+            ),
+            (
+                "  name  [ bar ]  >=1 ; extra == 'x'  ",
+                requirement("name", &["bar"], &[">=1"], None, Some("extra == 'x'")),
+            ),
+        ];
+        for (input, expected) in vectors {
+            assert_eq!(input.parse::<Requirement>(), Ok(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_requirements() {
+        let vectors = vec![
+            ("", 0..0, RequirementParseReason::InvalidName),
+            ("(>=1.0)", 0..1, RequirementParseReason::InvalidName),
+            ("-name", 0..5, RequirementParseReason::InvalidName),
+            ("name!", 4..5, RequirementParseReason::InvalidSpecifier),
+            (
+                "name[bar",
+                8..8,
+                RequirementParseReason::Expected("',' or ']'"),
+            ),
+            (
+                "name[bar baz]",
+                9..10,
+                RequirementParseReason::Expected("',' or ']'"),
+            ),
+            ("name[-bar]", 5..9, RequirementParseReason::InvalidExtra),
+            (
+                "name (>=1.0",
+                11..11,
+                RequirementParseReason::Expected("',' or ')'"),
+            ),
+            (
+                "name ()",
+                6..7,
+                RequirementParseReason::Expected("a version specifier"),
+            ),
+            (
+                "name>=1.0,,",
+                10..11,
+                RequirementParseReason::Expected("a version specifier"),
+            ),
+            (
+                "name @ ",
+                7..7,
+                RequirementParseReason::Expected("a URL after '@'"),
+            ),
+            (
+                "name >=1.0 foo",
+                11..12,
+                RequirementParseReason::Expected(
+                    "a version specifier, ';' or the end of the requirement",
+                ),
+            ),
+            (
+                "name;",
+                5..5,
+                RequirementParseReason::Expected("a marker variable or string"),
+            ),
+            (
+                "name; os_name == 'nt' extra",
+                22..23,
+                RequirementParseReason::Expected("'and', 'or' or the end of the marker"),
+            ),
+            (
+                "name @ http://example.com;extra == 'x'",
+                32..33,
+                RequirementParseReason::Expected(
+                    "a version specifier, ';' or the end of the requirement",
+                ),
+            ),
+            (
+                "name>=1.0.*",
+                6..11,
+                RequirementParseReason::UnexpectedWildcard(crate::ranges::Compare::GreaterOrEqual),
+            ),
+        ];
+        for (input, span, reason) in vectors {
+            let error = input.parse::<Requirement>().unwrap_err();
+            assert_eq!((input, error.span, error.reason), (input, span, reason));
+        }
+    }
+
+    #[test]
+    fn parse_invalid_version() {
+        let error = "name==1.0.org1".parse::<Requirement>().unwrap_err();
+        assert_eq!(error.input, "name==1.0.org1");
+        assert_eq!(&error.input[error.span], "1.0.org1");
+    }
+
+    #[test]
+    fn format_requirement() {
+        let requirement: Requirement = "name [fred, bar] (>= 1.0 , <2) ; python_version=='2.7'"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            requirement.to_string(),
+            "name[fred,bar]>=1.0,<2; python_version == \"2.7\""
+        );
+        let requirement: Requirement = "name@http://foo.com ; extra == 'x'".parse().unwrap();
+        assert_eq!(
+            requirement.to_string(),
+            "name @ http://foo.com ; extra == \"x\""
+        );
+        let requirement: Requirement = "foo @ https://x/y.whl".parse().unwrap();
+        assert_eq!(requirement.to_string(), "foo @ https://x/y.whl");
+        assert_eq!(requirement.to_string().parse(), Ok(requirement));
+    }
+
+    #[test]
+    fn normalize_names() {
+        for name in &[
+            "friendly-bard",
+            "Friendly-Bard",
+            "FRIENDLY-BARD",
+            "friendly.bard",
+            "friendly_bard",
+            "friendly--bard",
+            "FrIeNdLy-._.-bArD",
+        ] {
+            assert_eq!(normalize_name(name), "friendly-bard");
+        }
+    }
+
+    #[test]
+    fn extra_packages() {
+        assert_eq!(
+            extra_package("requests", "Socks_Proxy"),
+            "requests[socks-proxy]"
+        );
+        assert_eq!(
+            split_extra_package("requests[socks-proxy]"),
+            ("requests", Some("socks-proxy"))
+        );
+        assert_eq!(split_extra_package("requests"), ("requests", None));
+        assert_eq!(extra_package("PySocks", "x"), extra_package("pysocks", "X"));
+        let requirement: Requirement = "requests[security,socks]>=2.8".parse().unwrap();
+        let range = requirement.range();
+        assert_eq!(
+            requirement.dependencies().unwrap(),
+            vec![
+                ("requests".to_string(), range.clone()),
+                ("requests[security]".to_string(), range.clone()),
+                ("requests[socks]".to_string(), range),
+            ]
+        );
+        let spellings: Vec<Requirement> = vec![
+            "PySocks[Extra_One]".parse().unwrap(),
+            "pysocks[extra-one]".parse().unwrap(),
+        ];
+        assert_eq!(
+            spellings[0].dependencies().unwrap(),
+            vec![
+                ("pysocks".to_string(), Range::any()),
+                ("pysocks[extra-one]".to_string(), Range::any()),
+            ]
+        );
+        assert_eq!(spellings[0].dependencies(), spellings[1].dependencies());
+    }
+
+    #[test]
+    fn direct_reference_dependencies() {
+        for input in ["foo @ https://x/y.whl", "foo[bar] @ https://x/y.whl"] {
+            let requirement: Requirement = input.parse().unwrap();
+            let error = requirement.dependencies().unwrap_err();
+            assert_eq!(error.reason, RequirementParseReason::DirectReference);
+            assert_eq!(&error.input[error.span], "https://x/y.whl");
+        }
+    }
+
+    fn identifier() -> impl Strategy<Value = String> {
+        "[A-Za-z0-9]([A-Za-z0-9._-]{0,8}[A-Za-z0-9])?"
+    }
+
+    fn version_specifier() -> impl Strategy<Value = String> {
+        let operator = prop::sample::select(vec!["<", "<=", "!=", "==", ">=", ">", "~="]);
+        (
+            operator,
+            "[0-9]{1,4}(\\.[0-9]{1,3}){1,3}((a|b|rc)[0-9])?(\\.post[0-9])?(\\.dev[0-9])?",
+        )
+            .prop_map(|(operator, version)| format!("{}{}", operator, version))
+    }
+
+    fn marker() -> impl Strategy<Value = MarkerTree> {
+        prop::sample::select(vec![
+            "python_version < \"3.8\"",
+            "extra == 'security'",
+            "sys_platform == 'win32' and (python_version >= '3.6' or os_name != 'nt')",
+        ])
+        .prop_map(|marker| marker.parse().unwrap())
+    }
+
+    prop_compose! {
+        fn arbitrary_requirement()(
+            name in identifier(),
+            extras in prop::collection::vec(identifier(), 0..3),
+            version_spec in prop::collection::vec(version_specifier(), 0..3),
+            url in prop::option::of("https://example\\.com/[a-z]{1,8}\\.zip"),
+            marker in prop::option::of(marker()),
+        ) -> Requirement {
+            let version_spec = match url {
+                Some(_) => Vec::new(),
+                None => version_spec.iter().map(|s| s.parse().unwrap()).collect(),
+            };
+            Requirement { name, extras, version_spec, url, marker }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn display_round_trip(requirement in arbitrary_requirement()) {
+            let formatted = requirement.to_string();
+            prop_assert_eq!(formatted.parse::<Requirement>(), Ok(requirement), "{}", formatted);
+        }
+
+        #[test]
+        fn parse_never_panics(input in "\\PC*") {
+            let _ = input.parse::<Requirement>();
+        }
+
+        #[test]
+        fn parse_mutated_never_panics(
+            requirement in arbitrary_requirement(),
+            index in any::<prop::sample::Index>(),
+            insert in "[ \\[\\](),;@<>=!~.*+\\-]",
+        ) {
+            let mut formatted = requirement.to_string();
+            let position = index.index(formatted.len() + 1);
+            formatted.insert_str(position, &insert);
+            if let Err(error) = formatted.parse::<Requirement>() {
+                prop_assert!(error.span.end <= formatted.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,486 @@
+use crate::cache::MetadataCache;
+use crate::index::{IndexError, IndexRelease, PackageIndex, ReleaseFile, Remote, Retries};
+use crate::requirement::normalize_name;
+use crate::version::PEP440Version;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+lazy_static! {
+    static ref ANCHOR: Regex = Regex::new(r"(?is)<a\s([^>]*)>(.*?)</a\s*>").unwrap();
+    static ref ATTRIBUTE: Regex =
+        Regex::new(r#"([^\s=]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#).unwrap();
+    static ref ENTITY: Regex = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+}
+
+/// Extensions of source distributions, see [PEP 527](https://www.python.org/dev/peps/pep-0527/).
+const SDIST_EXTENSIONS: [&str; 6] = [".tar.gz", ".tar.bz2", ".tar.xz", ".tgz", ".tar", ".zip"];
+
+/// A file linked from the page of a project.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct ProjectFile {
+    filename: String,
+    /// Absolute URL of the file, without the hash.
+    url: String,
+    requires_python: Option<String>,
+    /// The reason given for yanking the file, empty without one, or `None` if it isn't yanked.
+    yanked: Option<String>,
+    /// Whether the core metadata of the file is served at `{url}.metadata`,
+    /// see [PEP 658](https://www.python.org/dev/peps/pep-0658/).
+    has_metadata: bool,
+}
+
+/// Replace the character references of HTML, like `&gt;` and `&#62;`.
+fn unescape(text: &str) -> String {
+    let replace = |captures: &Captures| {
+        let reference = &captures[1];
+        let character = match reference {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match reference.strip_prefix('#') {
+                Some(hex) if hex.starts_with(&['x', 'X'][..]) => u32::from_str_radix(&hex[1..], 16)
+                    .ok()
+                    .and_then(char::from_u32),
+                Some(decimal) => decimal.parse().ok().and_then(char::from_u32),
+                None => None,
+            },
+        };
+        character.map_or_else(|| captures[0].to_string(), String::from)
+    };
+    ENTITY.replace_all(text, replace).into_owned()
+}
+
+/// The files linked from the HTML page of a project at `page_url`.
+fn parse_project_page(page_url: &Url, html: &str) -> Vec<ProjectFile> {
+    let mut files = Vec::new();
+    for anchor in ANCHOR.captures_iter(html) {
+        let attributes: HashMap<String, String> = ATTRIBUTE
+            .captures_iter(&anchor[1])
+            .map(|attribute| {
+                let value = (2..=4).find_map(|i| attribute.get(i));
+                let value = value.map_or(String::new(), |value| unescape(value.as_str()));
+                (attribute[1].to_ascii_lowercase(), value)
+            })
+            .collect();
+        let url = attributes
+            .get("href")
+            .and_then(|href| page_url.join(href).ok());
+        let mut url = match url {
+            Some(url) => url,
+            None => continue,
+        };
+        url.set_fragment(None);
+        // PEP 714 renamed the attribute of PEP 658
+        let metadata = attributes
+            .get("data-core-metadata")
+            .or_else(|| attributes.get("data-dist-info-metadata"));
+        files.push(ProjectFile {
+            filename: unescape(anchor[2].trim()),
+            url: url.to_string(),
+            requires_python: attributes
+                .get("data-requires-python")
+                .filter(|requires_python| !requires_python.is_empty())
+                .cloned(),
+            yanked: attributes.get("data-yanked").cloned(),
+            has_metadata: metadata.is_some_and(|metadata| metadata != "false"),
+        });
+    }
+    files
+}
+
+/// The version in the file name of a wheel or a source distribution of `package`,
+/// or `None` for other kinds of files.
+fn filename_version<'a>(filename: &'a str, package: &str) -> Option<&'a str> {
+    let version = if let Some(stem) = filename.strip_suffix(".whl") {
+        // {name}-{version}(-{build tag})?-{python tag}-{abi tag}-{platform tag}.whl
+        let parts: Vec<&str> = stem.split('-').collect();
+        match parts.len() {
+            5 | 6 => parts[1],
+            _ => return None,
+        }
+    } else {
+        // {name}-{version}.tar.gz, where older ones don't escape dashes in the name
+        let stem = SDIST_EXTENSIONS
+            .iter()
+            .find_map(|extension| filename.strip_suffix(extension))?;
+        let package = normalize_name(package);
+        let (dash, _) = stem
+            .match_indices('-')
+            .find(|(dash, _)| normalize_name(&stem[..*dash]) == package)?;
+        &stem[dash + 1..]
+    };
+    Some(version).filter(|version| !version.is_empty())
+}
+
+/// The `Requires-Dist` fields of core metadata, which is formatted like the header of an email.
+fn metadata_requires_dist(metadata: &str) -> Vec<String> {
+    let mut requires_dist: Vec<String> = Vec::new();
+    let mut continued = false;
+    for line in metadata.lines() {
+        if line.is_empty() {
+            // the description may follow
+            break;
+        }
+        if line.starts_with(&[' ', '\t'][..]) {
+            if let (true, Some(last)) = (continued, requires_dist.last_mut()) {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+            continue;
+        }
+        let (name, value) = line.split_once(':').unwrap_or((line, ""));
+        continued = name.trim().eq_ignore_ascii_case("requires-dist");
+        if continued {
+            requires_dist.push(value.trim().to_string());
+        }
+    }
+    requires_dist
+}
+
+/// The [Simple Repository API](https://www.python.org/dev/peps/pep-0503/) of an index,
+/// like `https://pypi.org/simple/requests/`, which private indexes commonly serve.
+///
+/// Releases are taken from the names of the linked wheels and source distributions,
+/// leaving out files whose version is not a PEP 440 version.
+/// `Requires-Dist` is read from the metadata files some indexes serve next to the distributions.
+/// Releases without such a file fail with [IndexError::NoMetadataFile],
+/// and the resolver skips them rather than downloading distributions to read their metadata.
+pub struct SimpleIndex {
+    remote: Remote,
+    /// Files of the projects whose pages were fetched, by normalized name.
+    projects: RefCell<HashMap<String, Vec<ProjectFile>>>,
+}
+
+impl Default for SimpleIndex {
+    fn default() -> Self {
+        SimpleIndex::new("https://pypi.org/simple")
+    }
+}
+
+impl SimpleIndex {
+    /// The Simple API below `url`, which is `https://pypi.org/simple` for PyPI itself.
+    pub fn new(url: &str) -> Self {
+        SimpleIndex {
+            remote: Remote::new(url),
+            projects: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// See [PypiJsonIndex::with_cache](crate::index::PypiJsonIndex::with_cache).
+    pub fn with_cache(self, cache: MetadataCache) -> Self {
+        SimpleIndex {
+            remote: self.remote.with_cache(cache),
+            ..self
+        }
+    }
+
+    /// See [PypiJsonIndex::with_offline](crate::index::PypiJsonIndex::with_offline).
+    pub fn with_offline(self, offline: bool) -> Self {
+        SimpleIndex {
+            remote: self.remote.with_offline(offline),
+            ..self
+        }
+    }
+
+    /// See [PypiJsonIndex::with_retries](crate::index::PypiJsonIndex::with_retries).
+    pub fn with_retries(self, retries: Retries) -> Self {
+        SimpleIndex {
+            remote: self.remote.with_retries(retries),
+            ..self
+        }
+    }
+
+    /// The files on the page of `package`.
+    fn files(&self, package: &str) -> Result<Vec<ProjectFile>, Box<dyn Error>> {
+        let name = normalize_name(package);
+        if let Some(files) = self.projects.borrow().get(&name) {
+            return Ok(files.clone());
+        }
+        let url = format!("{}/{}/", self.remote.url, name);
+        let slot = self
+            .remote
+            .cache
+            .as_ref()
+            .map(|cache| cache.project(&self.remote.url, &name));
+        let files = self.remote.fetch(&url, package, None, slot, |fetched| {
+            let page_url = Url::parse(&fetched.url)?;
+            Ok(parse_project_page(
+                &page_url,
+                &String::from_utf8_lossy(&fetched.body),
+            ))
+        })?;
+        self.projects.borrow_mut().insert(name, files.clone());
+        Ok(files)
+    }
+}
+
+impl PackageIndex for SimpleIndex {
+    fn releases(&self, package: &str) -> Result<Vec<IndexRelease>, Box<dyn Error>> {
+        // files may spell the same version differently, like `1.0rc1` and `1.0-rc.1`
+        let mut releases: BTreeMap<PEP440Version, Vec<ReleaseFile>> = BTreeMap::new();
+        for file in self.files(package)? {
+            let version = filename_version(&file.filename, package)
+                .and_then(|version| version.parse::<PEP440Version>().ok());
+            let version = match version {
+                Some(version) => version,
+                None => continue,
+            };
+            releases.entry(version).or_default().push(ReleaseFile {
+                requires_python: file.requires_python,
+                yanked: file.yanked.is_some(),
+                yanked_reason: file.yanked.filter(|reason| !reason.is_empty()),
+            });
+        }
+        Ok(releases
+            .into_iter()
+            .map(|(version, files)| IndexRelease {
+                version: version.to_string(),
+                files,
+            })
+            .collect())
+    }
+
+    fn requires_dist(
+        &self,
+        package: &str,
+        version: &PEP440Version,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let files = self.files(package)?;
+        // wheels have static metadata, unlike source distributions
+        let (file, published) = files
+            .iter()
+            .filter(|file| file.has_metadata)
+            .filter_map(|file| Some((file, filename_version(&file.filename, package)?)))
+            .filter(|(_, published)| published.parse().ok().as_ref() == Some(version))
+            .min_by_key(|(file, _)| !file.filename.ends_with(".whl"))
+            .ok_or_else(|| IndexError::NoMetadataFile {
+                package: package.to_string(),
+                version: version.clone(),
+            })?;
+        let url = format!("{}.metadata", file.url);
+        let slot = self
+            .remote
+            .cache
+            .as_ref()
+            .map(|cache| cache.requires_dist(&self.remote.url, package, published));
+        self.remote
+            .fetch(&url, package, Some(version), slot, |fetched| {
+                Ok(metadata_requires_dist(&String::from_utf8_lossy(
+                    &fetched.body,
+                )))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::tests::{response, serve};
+    use crate::index::{IndexError, PackageIndex, ReleaseFile};
+    use crate::markers::Environment;
+    use crate::ranges::PrereleasePolicy;
+    use crate::resolve;
+    use crate::simple_index::{
+        filename_version, metadata_requires_dist, parse_project_page, unescape, ProjectFile,
+        SimpleIndex,
+    };
+    use reqwest::Url;
+
+    #[test]
+    fn test_filename_version() {
+        let version = |filename| filename_version(filename, "Foo.Bar");
+        assert_eq!(version("foo_bar-1.0-py3-none-any.whl"), Some("1.0"));
+        assert_eq!(
+            version("foo_bar-1.0-2-cp39-cp39-manylinux1_x86_64.whl"),
+            Some("1.0")
+        );
+        assert_eq!(version("foo_bar-1.0.tar.gz"), Some("1.0"));
+        assert_eq!(version("Foo.Bar-1.0rc1.zip"), Some("1.0rc1"));
+        assert_eq!(version("foo-bar-1.0-1.tar.gz"), Some("1.0-1"));
+        assert_eq!(version("foo_bar-1.0-py3.8.egg"), None);
+        assert_eq!(version("foo_bar-1.0.whl"), None);
+        assert_eq!(version("foo-baz-1.0.tar.gz"), None);
+        assert_eq!(version("foo_bar-.tar.gz"), None);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("&gt;=3.6, !=3.0.*"), ">=3.6, !=3.0.*");
+        assert_eq!(unescape("&#62;&#x3C;&amp;lt;"), "><&lt;");
+        assert_eq!(unescape("&unknown; & &#xZZ;"), "&unknown; & &#xZZ;");
+    }
+
+    #[test]
+    fn test_metadata_requires_dist() {
+        let metadata = "Metadata-Version: 2.1\nName: foo\nRequires-Dist: bar (>=1)\n\
+                        requires-dist: baz; extra == 'x'\nSummary: Requires-Dist: no\n\
+                        Requires-Dist: qux\n  ; python_version < '3.8'\n\n\
+                        Requires-Dist: in the description\n";
+        assert_eq!(
+            metadata_requires_dist(metadata),
+            vec![
+                "bar (>=1)",
+                "baz; extra == 'x'",
+                "qux ; python_version < '3.8'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_project_page() {
+        let page_url = Url::parse("https://example.org/simple/foo/").unwrap();
+        let html = r#"<!DOCTYPE html>
+            <html><body>
+            <h1>Links for foo</h1>
+            <a href="../../files/foo-1.0.tar.gz#sha256=abc">foo-1.0.tar.gz</a><br/>
+            <A HREF='https://cdn.example.org/foo-1.1-py3-none-any.whl'
+               data-requires-python="&gt;=3.6" data-dist-info-metadata="sha256=def"
+               data-yanked>foo-1.1-py3-none-any.whl</A>
+            <a href="foo-1.2.tar.gz" data-yanked="broken &amp; slow" data-core-metadata="false">
+              foo-1.2.tar.gz
+            </a>
+            <a name="no-link">nothing</a>
+            </body></html>"#;
+        let file = |filename: &str, url: &str| ProjectFile {
+            filename: filename.to_string(),
+            url: url.to_string(),
+            requires_python: None,
+            yanked: None,
+            has_metadata: false,
+        };
+        assert_eq!(
+            parse_project_page(&page_url, html),
+            vec![
+                file("foo-1.0.tar.gz", "https://example.org/files/foo-1.0.tar.gz"),
+                ProjectFile {
+                    requires_python: Some(">=3.6".into()),
+                    yanked: Some("".into()),
+                    has_metadata: true,
+                    ..file(
+                        "foo-1.1-py3-none-any.whl",
+                        "https://cdn.example.org/foo-1.1-py3-none-any.whl"
+                    )
+                },
+                ProjectFile {
+                    yanked: Some("broken & slow".into()),
+                    ..file(
+                        "foo-1.2.tar.gz",
+                        "https://example.org/simple/foo/foo-1.2.tar.gz"
+                    )
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simple_index() {
+        let (url, requests) = serve(|request| {
+            let path = request.split(' ').nth(1).unwrap_or_default();
+            let (status, body) = match path {
+                "/simple/foo-bar/" => (
+                    "200 OK",
+                    r#"<a href="/files/Foo.Bar-1.0.tar.gz">Foo.Bar-1.0.tar.gz</a>
+                    <a href="/files/foo_bar-1.0-py3-none-any.whl" data-core-metadata="true"
+                       data-requires-python="&gt;=3.6">foo_bar-1.0-py3-none-any.whl</a>
+                    <a href="/files/foo_bar-2.0-py3-none-any.whl"
+                       data-yanked="">foo_bar-2.0-py3-none-any.whl</a>
+                    <a href="/files/foo_bar-2.0.0.tar.gz">foo_bar-2.0.0.tar.gz</a>
+                    <a href="/files/foo_bar-nightly.tar.gz">foo_bar-nightly.tar.gz</a>"#,
+                ),
+                "/files/foo_bar-1.0-py3-none-any.whl.metadata" => {
+                    ("200 OK", "Name: foo-bar\nRequires-Dist: baz (>=1)\n")
+                }
+                _ => ("404 Not Found", ""),
+            };
+            response(status, "", body)
+        });
+        let index = SimpleIndex::new(&format!("{}/simple/", url));
+        let releases = index.releases("Foo_Bar").unwrap();
+        let versions: Vec<_> = releases.iter().map(|r| r.version.as_str()).collect();
+        assert_eq!(versions, vec!["1.0", "2.0"]);
+        assert_eq!(
+            releases[0].files,
+            vec![
+                ReleaseFile::default(),
+                ReleaseFile {
+                    requires_python: Some(">=3.6".into()),
+                    ..ReleaseFile::default()
+                }
+            ]
+        );
+        assert!(releases[1].files[0].yanked);
+        assert_eq!(releases[1].files[0].yanked_reason, None);
+        assert_eq!(releases[1].files.len(), 2);
+
+        let one = "1.0".parse().unwrap();
+        assert_eq!(
+            index.requires_dist("foo-bar", &one).unwrap(),
+            vec!["baz (>=1)"]
+        );
+        let error = index
+            .requires_dist("foo-bar", &"2.0".parse().unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(IndexError::NoMetadataFile { .. })
+        ));
+        let error = index.releases("missing").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(IndexError::NotFound { .. })
+        ));
+        let requests = requests.lock().unwrap();
+        let paths: Vec<_> = requests
+            .iter()
+            .map(|r| r.split(' ').nth(1).unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/simple/foo-bar/",
+                "/files/foo_bar-1.0-py3-none-any.whl.metadata",
+                "/simple/missing/"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skip_release_without_metadata_file() {
+        let (url, _) = serve(|request| {
+            let path = request.split(' ').nth(1).unwrap_or_default();
+            let (status, body) = match path {
+                "/simple/foo/" => (
+                    "200 OK",
+                    r#"<a href="/files/foo-1.0-py3-none-any.whl" data-dist-info-metadata="true"
+                       >foo-1.0-py3-none-any.whl</a>
+                    <a href="/files/foo-2.0-py3-none-any.whl">foo-2.0-py3-none-any.whl</a>"#,
+                ),
+                "/files/foo-1.0-py3-none-any.whl.metadata" => ("200 OK", "Name: foo\n"),
+                _ => ("404 Not Found", ""),
+            };
+            response(status, "", body)
+        });
+        let index = SimpleIndex::new(&format!("{}/simple", url));
+        let solution = resolve(
+            "root",
+            "1.0",
+            vec![("foo", "*")],
+            vec![],
+            &Environment::default(),
+            &PrereleasePolicy::default(),
+            index,
+        )
+        .unwrap();
+        let versions: Vec<_> = solution
+            .iter()
+            .map(|package| format!("{} {}", package.name, package.version))
+            .collect();
+        assert_eq!(versions, vec!["foo 1.0"]);
+    }
+}
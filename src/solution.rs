@@ -0,0 +1,205 @@
+use crate::requirement::split_extra_package;
+use crate::version::PEP440Version;
+use pubgrub::solver::{Dependencies, DependencyProvider};
+use pubgrub::type_aliases::SelectedDependencies;
+use pyo3::prelude::*;
+use pyo3::types::IntoPyDict;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Whether a package is needed by the project itself or only for development.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// Required through `requires`, possibly also through `dev_requires`.
+    Main,
+    /// Only required through `dev_requires`.
+    Dev,
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Main => write!(f, "main"),
+            Self::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+/// A package of the solution with the edges of the dependency graph it is part of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPackage {
+    pub name: String,
+    pub version: PEP440Version,
+    /// Extras of the package that were requested by any of its dependents.
+    pub extras: Vec<String>,
+    /// Packages this package depends on.
+    pub dependencies: Vec<String>,
+    /// Packages depending on this package, including the root package.
+    pub required_by: Vec<String>,
+    pub category: Category,
+    /// Problems with the picked version that don't prevent using it, like being yanked.
+    pub warnings: Vec<String>,
+}
+
+/// A dict with the same keys as the fields, the version as a string
+/// and the category either `"main"` or `"dev"`.
+impl IntoPy<PyObject> for ResolvedPackage {
+    fn into_py(self, py: Python) -> PyObject {
+        vec![
+            ("name", self.name.into_py(py)),
+            ("version", self.version.to_string().into_py(py)),
+            ("extras", self.extras.into_py(py)),
+            ("dependencies", self.dependencies.into_py(py)),
+            ("required_by", self.required_by.into_py(py)),
+            ("category", self.category.to_string().into_py(py)),
+            ("warnings", self.warnings.into_py(py)),
+        ]
+        .into_py_dict(py)
+        .into()
+    }
+}
+
+/// Collect the packages of a `solution` found for `root`, leaving out `root` itself.
+///
+/// Dependencies are asked from `provider` again to know the edges of the graph.
+/// Virtual packages for extras are merged into their base package.
+/// Packages reachable from one of the `main` dependencies of `root` are [Category::Main],
+/// all others are [Category::Dev].
+pub fn collect_solution<DP: DependencyProvider<String, PEP440Version>>(
+    provider: &DP,
+    root: &str,
+    solution: &SelectedDependencies<String, PEP440Version>,
+    main: &[String],
+) -> Result<Vec<ResolvedPackage>, Box<dyn Error>> {
+    let mut packages: BTreeMap<&str, ResolvedPackage> = BTreeMap::new();
+    let mut edges: BTreeSet<(&str, &str)> = BTreeSet::new();
+    for (package, version) in solution {
+        let (name, extra) = split_extra_package(package);
+        let dependencies = match provider.get_dependencies(package, version)? {
+            Dependencies::Known(dependencies) => dependencies,
+            Dependencies::Unknown => Default::default(),
+        };
+        for dependency in dependencies.keys() {
+            let dependency = solution
+                .get_key_value(dependency)
+                .map(|(d, _)| split_extra_package(d).0)
+                .ok_or_else(|| format!("{} is missing from the solution", dependency))?;
+            if dependency != name {
+                edges.insert((name, dependency));
+            }
+        }
+        if name == root {
+            continue;
+        }
+        let resolved = packages.entry(name).or_insert_with(|| ResolvedPackage {
+            name: name.to_string(),
+            version: version.clone(),
+            extras: Vec::new(),
+            dependencies: Vec::new(),
+            required_by: Vec::new(),
+            category: Category::Dev,
+            warnings: Vec::new(),
+        });
+        if let Some(extra) = extra {
+            resolved.extras.push(extra.to_string());
+            resolved.extras.sort();
+        }
+    }
+    for (from, to) in &edges {
+        if let Some(resolved) = packages.get_mut(from) {
+            resolved.dependencies.push(to.to_string());
+        }
+        if let Some(resolved) = packages.get_mut(to) {
+            resolved.required_by.push(from.to_string());
+        }
+    }
+
+    let mut pending: Vec<&str> = main
+        .iter()
+        .map(|package| split_extra_package(package).0)
+        .collect();
+    let mut seen: HashSet<&str> = HashSet::new();
+    while let Some(name) = pending.pop() {
+        if !seen.insert(name) {
+            continue;
+        }
+        if let Some(resolved) = packages.get_mut(name) {
+            resolved.category = Category::Main;
+        }
+        pending.extend(
+            edges
+                .range((name, "")..)
+                .take_while(|(from, _)| *from == name)
+                .map(|(_, to)| *to),
+        );
+    }
+    Ok(packages.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solution::{collect_solution, Category, ResolvedPackage};
+    use crate::version::PEP440Version;
+    use pubgrub::range::Range;
+    use pubgrub::solver::{resolve, OfflineDependencyProvider};
+
+    #[test]
+    fn collect_edges_extras_and_categories() {
+        let mut provider = OfflineDependencyProvider::<String, PEP440Version>::new();
+        let one = PEP440Version::one();
+        let exact = || Range::exact(PEP440Version::one());
+        let package = |name: &str| (name.to_string(), Range::any());
+        provider.add_dependencies(
+            "root".into(),
+            one.clone(),
+            vec![package("requests[security]"), package("pytest")],
+        );
+        provider.add_dependencies("requests".into(), one.clone(), vec![package("idna")]);
+        provider.add_dependencies(
+            "requests[security]".into(),
+            one.clone(),
+            vec![("requests".to_string(), exact()), package("pyopenssl")],
+        );
+        provider.add_dependencies(
+            "pytest".into(),
+            one.clone(),
+            vec![package("idna"), package("py")],
+        );
+        for name in &["idna", "pyopenssl", "py"] {
+            provider.add_dependencies(name.to_string(), one.clone(), vec![]);
+        }
+        let solution = resolve(&provider, "root".into(), one.clone()).unwrap();
+
+        let packages =
+            collect_solution(&provider, "root", &solution, &["requests[security]".into()]).unwrap();
+        let resolved =
+            |name: &str, extras: &[&str], dependencies: &[&str], required_by: &[&str], category| {
+                ResolvedPackage {
+                    name: name.into(),
+                    version: one.clone(),
+                    extras: extras.iter().map(|e| e.to_string()).collect(),
+                    dependencies: dependencies.iter().map(|e| e.to_string()).collect(),
+                    required_by: required_by.iter().map(|e| e.to_string()).collect(),
+                    category,
+                    warnings: Vec::new(),
+                }
+            };
+        assert_eq!(
+            packages,
+            vec![
+                resolved("idna", &[], &[], &["pytest", "requests"], Category::Main),
+                resolved("py", &[], &[], &["pytest"], Category::Dev),
+                resolved("pyopenssl", &[], &[], &["requests"], Category::Main),
+                resolved("pytest", &[], &["idna", "py"], &["root"], Category::Dev),
+                resolved(
+                    "requests",
+                    &["security"],
+                    &["idna", "pyopenssl"],
+                    &["root"],
+                    Category::Main
+                ),
+            ]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use core::cmp::{Ord, Ordering, PartialOrd};
 use core::fmt::Display;
+use core::hash::{Hash, Hasher};
 use core::option::Option::{None, Some};
 use core::result::Result::{Err, Ok};
 use lazy_static::lazy_static;
@@ -11,22 +12,22 @@ use thiserror::Error;
 
 lazy_static! {
    // copied from packaging python package
-   pub static ref VERSION_PATTERN: Regex = Regex::new(r"^v?(?:(?:(?P<epoch>[0-9]+)!)?(?P<release>[0-9]+(?:\.[0-9]+)*)(?P<pre>[-_\.]?(?P<pre_l>(a|b|c|rc|alpha|beta|pre|preview))[-_\.]?(?P<pre_n>[0-9]+)?)?(?P<post>(?:-(?P<post_n1>[0-9]+))|(?:[-_\.]?(?P<post_l>post|rev|r)[-_\.]?(?P<post_n2>[0-9]+)?))?(?P<dev>[-_\.]?(?P<dev_l>dev)[-_\.]?(?P<dev_n>[0-9]+)?)?)(?:\+(?P<local>[a-z0-9]+(?:[-_\.][a-z0-9]+)*))?$").unwrap();
+   pub static ref VERSION_PATTERN: Regex = Regex::new(r"(?i)^v?(?:(?:(?P<epoch>[0-9]+)!)?(?P<release>[0-9]+(?:\.[0-9]+)*)(?P<pre>[-_\.]?(?P<pre_l>(a|b|c|rc|alpha|beta|pre|preview))[-_\.]?(?P<pre_n>[0-9]+)?)?(?P<post>(?:-(?P<post_n1>[0-9]+))|(?:[-_\.]?(?P<post_l>post|rev|r)[-_\.]?(?P<post_n2>[0-9]+)?))?(?P<dev>[-_\.]?(?P<dev_l>dev)[-_\.]?(?P<dev_n>[0-9]+)?)?)(?:\+(?P<local>[a-z0-9]+(?:[-_\.][a-z0-9]+)*))?$").unwrap();
 }
 
-/// Error creating [SemanticVersion] from [String].
+/// Error creating [PEP440Version] from [String].
 #[derive(Error, Debug, PartialEq)]
 pub enum VersionParseError {
-    /// [SemanticVersion] must contain major, minor, patch versions.
-    #[error("version {full_version} must contain 3 numbers separated by dot")]
-    NotThreeParts {
-        /// [SemanticVersion] that was being parsed.
+    /// [PEP440Version] must match [VERSION_PATTERN].
+    #[error("version {full_version} is not a valid PEP 440 version")]
+    InvalidVersion {
+        /// [PEP440Version] that was being parsed.
         full_version: String,
     },
     /// Wrapper around [ParseIntError](core::num::ParseIntError).
     #[error("cannot parse '{version_part}' in '{full_version}' as u32: {parse_error}")]
     ParseIntError {
-        /// [SemanticVersion] that was being parsed.
+        /// [PEP440Version] that was being parsed.
         full_version: String,
         /// A version part where parsing failed.
         version_part: String,
@@ -40,28 +41,83 @@ pub enum VersionParseError {
     },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Sort key of an optional version segment,
+/// mirroring the infinities used by the `packaging` python package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Key<T> {
+    NegativeInfinity,
+    Finite(T),
+    Infinity,
+}
+
+/// A PEP 440 version.
+///
+/// The release segment can have any number of components.
+/// Missing trailing components are treated as zeros,
+/// so `1.2`, `1.2.0` and `1.2.0.0` are all equal.
+///
+/// A version can also be an arbitrary string, see [PEP440Version::from_arbitrary].
+#[derive(Debug, Clone)]
 pub struct PEP440Version {
-    pub major: u32,
-    pub minor: u32,
-    pub patch: u32,
     pub epoch: u32,
+    pub release: Vec<u32>,
     pub pre: Option<(Prerelease, u32)>,
     pub post: Option<u32>,
     pub dev: Option<u32>,
+    /// Local version label, empty for public versions.
+    pub local: Vec<LocalSegment>,
+    /// Version string that is only compared as a string, all other fields are unused.
+    pub arbitrary: Option<String>,
+    /// Sort above every post release of this version, see [PEP440Version::bump_post_releases].
+    /// No published version has this set, it only bounds ranges.
+    pub(crate) above_post_releases: bool,
 }
 
 impl PEP440Version {
     pub fn new(major: u32, minor: u32, patch: u32) -> PEP440Version {
-        return PEP440Version {
-            major,
-            minor,
-            patch,
+        PEP440Version::from_release(vec![major, minor, patch])
+    }
+
+    pub fn from_release(release: Vec<u32>) -> PEP440Version {
+        PEP440Version {
             epoch: 0,
+            release,
             pre: None,
             post: None,
             dev: None,
-        };
+            local: Vec::new(),
+            arbitrary: None,
+            above_post_releases: false,
+        }
+    }
+
+    /// A version compared by its string only, as used by arbitrary equality (`===`).
+    /// This covers published versions that are not valid PEP 440.
+    /// Like legacy versions in the `packaging` python package,
+    /// arbitrary versions sort before all PEP 440 versions
+    /// and compare case-insensitively between themselves.
+    pub fn from_arbitrary(version: &str) -> Self {
+        PEP440Version {
+            arbitrary: Some(version.to_string()),
+            ..PEP440Version::from_release(Vec::new())
+        }
+    }
+
+    pub fn is_arbitrary(&self) -> bool {
+        self.arbitrary.is_some()
+    }
+
+    /// `0.dev0` sorts before every PEP 440 version.
+    pub fn lowest_pep440() -> Self {
+        PEP440Version {
+            dev: Some(0),
+            ..PEP440Version::from_release(vec![0])
+        }
+    }
+
+    /// Lower-cased arbitrary string, used for comparisons.
+    fn arbitrary_key(&self) -> Option<String> {
+        self.arbitrary.as_ref().map(|s| s.to_lowercase())
     }
 
     pub fn zero() -> Self {
@@ -72,31 +128,139 @@ impl PEP440Version {
         PEP440Version::new(1, 0, 0)
     }
 
-    pub fn bump_major(&self) -> Self {
+    /// Release component at `index`, zero if the release is shorter.
+    pub fn release_part(&self, index: usize) -> u32 {
+        self.release.get(index).copied().unwrap_or(0)
+    }
+
+    /// Release without trailing zeros, used for comparisons.
+    fn trimmed_release(&self) -> &[u32] {
+        let len = self
+            .release
+            .iter()
+            .rposition(|&part| part != 0)
+            .map_or(0, |i| i + 1);
+        &self.release[..len]
+    }
+
+    /// Sort key of the pre-release segment.
+    /// A dev release without pre or post segment sorts before every pre-release,
+    /// a version without pre-release sorts after all of them.
+    fn pre_key(&self) -> Key<(Prerelease, u32)> {
+        match (self.pre, self.post, self.dev) {
+            (None, None, Some(_)) => Key::NegativeInfinity,
+            (None, _, _) => Key::Infinity,
+            (Some(pre), _, _) => Key::Finite(pre),
+        }
+    }
+
+    /// Sort key of the post-release segment, versions without one sort first.
+    fn post_key(&self) -> Key<u32> {
+        match self.post {
+            _ if self.above_post_releases => Key::Infinity,
+            None => Key::NegativeInfinity,
+            Some(post) => Key::Finite(post),
+        }
+    }
+
+    /// Sort key of the dev segment, versions without one sort last.
+    fn dev_key(&self) -> Key<u32> {
+        match self.dev {
+            None => Key::Infinity,
+            Some(dev) => Key::Finite(dev),
+        }
+    }
+
+    /// The public version, without the local label.
+    pub fn public(&self) -> Self {
         PEP440Version {
-            major: self.major + 1,
-            ..*self
+            local: Vec::new(),
+            ..self.clone()
         }
     }
 
-    pub fn bump_minor(&self) -> Self {
+    /// The smallest version higher than this version and all of its local variants.
+    /// There is no room between a version and its first dev or post release,
+    /// so this is the next dev release if there is a dev segment
+    /// and the first dev release of the next post release otherwise.
+    pub fn bump_public(&self) -> Self {
+        match (self.post, self.dev) {
+            (_, Some(_)) => self.public().bump_dev(),
+            (_, None) => PEP440Version {
+                dev: Some(0),
+                ..self.public().bump_post()
+            },
+        }
+    }
+
+    /// A version higher than this version, all of its post releases and their local variants,
+    /// but lower than any other version above them.
+    /// It is not a real version and only serves as the bound of a range.
+    pub fn bump_post_releases(&self) -> Self {
         PEP440Version {
-            minor: self.minor + 1,
-            ..*self
+            post: None,
+            dev: None,
+            above_post_releases: true,
+            ..self.public()
         }
     }
 
-    pub fn bump_patch(&self) -> Self {
+    pub fn with_local(&self, local: Vec<LocalSegment>) -> Self {
+        PEP440Version {
+            local,
+            ..self.clone()
+        }
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some() || self.dev.is_some()
+    }
+
+    pub fn is_postrelease(&self) -> bool {
+        self.post.is_some()
+    }
+
+    pub fn with_epoch(&self, epoch: u32) -> Self {
+        PEP440Version {
+            epoch,
+            ..self.clone()
+        }
+    }
+
+    pub fn bump_epoch(&self) -> Self {
+        self.with_epoch(self.epoch + 1)
+    }
+
+    /// Increment the release component at `index`,
+    /// padding the release with zeros if it is too short.
+    pub fn bump_release(&self, index: usize) -> Self {
+        let mut release = self.release.clone();
+        if release.len() <= index {
+            release.resize(index + 1, 0);
+        }
+        release[index] += 1;
         PEP440Version {
-            patch: self.patch + 1,
-            ..*self
+            release,
+            ..self.clone()
         }
     }
 
+    pub fn bump_major(&self) -> Self {
+        self.bump_release(0)
+    }
+
+    pub fn bump_minor(&self) -> Self {
+        self.bump_release(1)
+    }
+
+    pub fn bump_patch(&self) -> Self {
+        self.bump_release(2)
+    }
+
     pub fn pre_release(&self, kind: Prerelease) -> Self {
         PEP440Version {
             pre: Some((kind, 0)),
-            ..*self
+            ..self.clone()
         }
     }
 
@@ -107,7 +271,7 @@ impl PEP440Version {
         };
         PEP440Version {
             post: Some(post),
-            ..*self
+            ..self.clone()
         }
     }
 
@@ -118,21 +282,33 @@ impl PEP440Version {
         };
         PEP440Version {
             dev: Some(dev),
-            ..*self
+            ..self.clone()
         }
     }
 }
 
 impl Ord for PEP440Version {
     fn cmp(&self, other: &Self) -> Ordering {
-        (self.major, self.minor, self.patch, self.pre, self.post, self.dev).cmp(&(
-            other.major,
-            other.minor,
-            other.patch,
-            other.pre,
-            other.post,
-            other.dev,
-        ))
+        (
+            !self.is_arbitrary(),
+            self.arbitrary_key(),
+            self.epoch,
+            self.trimmed_release(),
+            self.pre_key(),
+            self.post_key(),
+            self.dev_key(),
+            &self.local,
+        )
+            .cmp(&(
+                !other.is_arbitrary(),
+                other.arbitrary_key(),
+                other.epoch,
+                other.trimmed_release(),
+                other.pre_key(),
+                other.post_key(),
+                other.dev_key(),
+                &other.local,
+            ))
     }
 }
 
@@ -142,37 +318,71 @@ impl PartialOrd for PEP440Version {
     }
 }
 
+impl PartialEq for PEP440Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PEP440Version {}
+
+impl Hash for PEP440Version {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.arbitrary_key().hash(state);
+        self.epoch.hash(state);
+        self.trimmed_release().hash(state);
+        self.pre_key().hash(state);
+        self.post_key().hash(state);
+        self.dev_key().hash(state);
+        self.local.hash(state);
+    }
+}
+
 impl Display for PEP440Version {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(arbitrary) = &self.arbitrary {
+            return write!(f, "{}", arbitrary);
+        }
+        if self.epoch != 0 {
+            write!(f, "{}!", self.epoch)?
+        }
+        let release: Vec<String> = self.release.iter().map(|part| part.to_string()).collect();
+        write!(f, "{}", release.join("."))?;
         if let Some((n, v)) = self.pre {
             write!(f, "{}{}", n, v)?
         }
         if let Some(post) = self.post {
-            write!(f, "post{}", post)?
+            write!(f, ".post{}", post)?
         }
         if let Some(dev) = self.dev {
-            write!(f, "dev{}", dev)?
+            write!(f, ".dev{}", dev)?
+        }
+        if !self.local.is_empty() {
+            let local: Vec<String> = self.local.iter().map(|part| part.to_string()).collect();
+            write!(f, "+{}", local.join("."))?
         }
         Ok(())
     }
 }
 
 impl Version for PEP440Version {
+    /// The empty arbitrary string sorts before every other version.
     fn lowest() -> Self {
-        PEP440Version::zero()
+        PEP440Version::from_arbitrary("")
     }
 
+    /// The smallest version strictly higher than this one.
+    /// An empty alphanumeric local segment sorts before every valid local segment,
+    /// so appending one yields the successor.
+    /// Likewise, appending a nul character to an arbitrary string yields its successor.
+    /// Neither is a valid version and only used as an exclusive bound.
     fn bump(&self) -> Self {
-        match (self.pre, self.post, self.dev) {
-            (None, None, None) => PEP440Version::new(self.major, self.minor, self.patch + 1),
-            (Some((k, v)), None, None) => PEP440Version {
-                pre: Some((k, v + 1)),
-                ..*self
-            },
-            (_, Some(_), None) => self.bump_post(),
-            (_, _, Some(_)) => self.bump_dev(),
+        if let Some(arbitrary) = &self.arbitrary {
+            return PEP440Version::from_arbitrary(&format!("{}\0", arbitrary));
         }
+        let mut local = self.local.clone();
+        local.push(LocalSegment::Alphanumeric(String::new()));
+        self.with_local(local)
     }
 }
 
@@ -188,54 +398,99 @@ impl FromStr for PEP440Version {
             })
         };
 
-        let captures = VERSION_PATTERN.captures(s);
+        let captures = VERSION_PATTERN.captures(s.trim());
         if let Some(c) = captures {
             if let Some(release) = c.name("release") {
-                let dev = match c.name("dev_n") {
-                    Some(m) => Some(parse_u32(m.as_str())?),
-                    None => None,
+                let epoch = match c.name("epoch") {
+                    Some(m) => parse_u32(m.as_str())?,
+                    None => 0,
+                };
+                // a missing number after a pre, post or dev label means 0
+                let number = |m: Option<regex::Match>| match m {
+                    Some(m) => parse_u32(m.as_str()),
+                    None => Ok(0),
                 };
-                let post = match c.name("post_n2") {
-                    Some(m) => Some(parse_u32(m.as_str())?),
+                let dev = match c.name("dev_l") {
+                    Some(_) => Some(number(c.name("dev_n"))?),
                     None => None,
                 };
-                let pre = match (c.name("pre_l"), c.name("pre_n")) {
-                    (Some(name), Some(version)) => {
-                        Some((name.as_str().parse()?, parse_u32(version.as_str())?))
-                    }
-                    (_, _) => None,
+                let post = match (c.name("post_n1"), c.name("post_l")) {
+                    (Some(implicit), _) => Some(parse_u32(implicit.as_str())?),
+                    (None, Some(_)) => Some(number(c.name("post_n2"))?),
+                    (None, None) => None,
                 };
-                let mut parts = release.as_str().split(".");
-                let (major, minor, patch) = match (parts.next(), parts.next(), parts.next()) {
-                    (Some(major), Some(minor), Some(patch)) => {
-                        (parse_u32(major)?, parse_u32(minor)?, parse_u32(patch)?)
-                    }
-                    (Some(major), Some(minor), None) => (parse_u32(major)?, parse_u32(minor)?, 0),
-                    (Some(major), None, None) => (parse_u32(major)?, 0, 0),
-                    _ => {
-                        return Err(VersionParseError::NotThreeParts {
-                            full_version: s.into(),
-                        })
-                    }
+                let pre = match c.name("pre_l") {
+                    Some(name) => Some((
+                        name.as_str().to_lowercase().parse()?,
+                        number(c.name("pre_n"))?,
+                    )),
+                    None => None,
+                };
+                let release = release
+                    .as_str()
+                    .split('.')
+                    .map(parse_u32)
+                    .collect::<Result<Vec<u32>, _>>()?;
+                let local = match c.name("local") {
+                    Some(local) => local
+                        .as_str()
+                        .split(['.', '-', '_'])
+                        .map(|part| LocalSegment::parse(part, s))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    None => Vec::new(),
                 };
                 return Ok(PEP440Version {
-                    major,
-                    minor,
-                    patch,
-                    epoch: 0,
+                    epoch,
+                    release,
                     pre,
                     dev,
                     post,
+                    local,
+                    arbitrary: None,
+                    above_post_releases: false,
                 });
             }
         }
-        Err(VersionParseError::NotThreeParts {
+        Err(VersionParseError::InvalidVersion {
             full_version: s.into(),
         })
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy, PartialOrd, Ord)]
+/// Segment of a local version label.
+/// Alphanumeric segments sort before numeric ones.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LocalSegment {
+    Alphanumeric(String),
+    Numeric(u64),
+}
+
+impl LocalSegment {
+    fn parse(part: &str, full_version: &str) -> Result<Self, VersionParseError> {
+        if part.chars().all(|c| c.is_ascii_digit()) {
+            part.parse()
+                .map(LocalSegment::Numeric)
+                .map_err(|e| VersionParseError::ParseIntError {
+                    full_version: full_version.to_string(),
+                    version_part: part.to_string(),
+                    parse_error: e.to_string(),
+                })
+        } else {
+            Ok(LocalSegment::Alphanumeric(part.to_lowercase()))
+        }
+    }
+}
+
+impl Display for LocalSegment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocalSegment::Alphanumeric(s) => write!(f, "{}", s),
+            LocalSegment::Numeric(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, PartialOrd, Ord)]
 pub enum Prerelease {
     Alpha,
     Beta,
@@ -271,8 +526,10 @@ impl FromStr for Prerelease {
 #[cfg(test)]
 mod tests {
     use crate::version::VERSION_PATTERN;
-    use crate::version::{PEP440Version, Prerelease};
+    use crate::version::{LocalSegment, PEP440Version, Prerelease};
+    use proptest::prelude::*;
     use pubgrub::version::Version;
+    use std::collections::HashSet;
 
     #[test]
     fn version_pattern() {
@@ -316,6 +573,165 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_version_epoch() {
+        assert_eq!(
+            "1!0.5".parse(),
+            Ok(PEP440Version::new(0, 5, 0).with_epoch(1))
+        );
+        assert_eq!("0!1.0".parse(), Ok(PEP440Version::one()));
+        assert_eq!(
+            "2!1.0.0a0".parse(),
+            Ok(PEP440Version::one()
+                .pre_release(Prerelease::Alpha)
+                .with_epoch(2))
+        );
+    }
+
+    #[test]
+    fn compare_version_epoch() {
+        // orderings taken from the `packaging` test suite
+        let ordered = ["2020.1", "1!0.5", "1!1.0", "1!1.0.1", "2!0.1"];
+        for window in ordered.windows(2) {
+            let lower: PEP440Version = window[0].parse().unwrap();
+            let higher: PEP440Version = window[1].parse().unwrap();
+            assert!(lower < higher, "{} < {}", lower, higher);
+        }
+        assert_eq!(
+            "0!1.0".parse::<PEP440Version>().unwrap(),
+            "1.0".parse::<PEP440Version>().unwrap()
+        );
+    }
+
+    #[test]
+    fn format_version_epoch() {
+        assert_eq!(format!("{}", PEP440Version::one().with_epoch(0)), "1.0.0");
+        assert_eq!(format!("{}", PEP440Version::one().with_epoch(1)), "1!1.0.0");
+        assert_eq!(
+            format!("{}", "3!2.0rc1".parse::<PEP440Version>().unwrap()),
+            "3!2.0rc1"
+        );
+    }
+
+    #[test]
+    fn bump_keeps_epoch() {
+        let version = PEP440Version::one().with_epoch(1);
+        assert_eq!(version.bump().epoch, 1);
+        assert_eq!(version.bump_major().epoch, 1);
+        assert_eq!(version.bump_dev().bump().epoch, 1);
+        assert_eq!(version.bump_epoch(), PEP440Version::one().with_epoch(2));
+    }
+
+    #[test]
+    fn parse_release_segments() {
+        let version: PEP440Version = "2020.12.5.1".parse().unwrap();
+        assert_eq!(version.release, vec![2020, 12, 5, 1]);
+        let version: PEP440Version = "0.4.0.post1.dev2".parse().unwrap();
+        assert_eq!(version.release, vec![0, 4, 0]);
+        assert_eq!(version.post, Some(1));
+        assert_eq!(version.dev, Some(2));
+    }
+
+    #[test]
+    fn compare_release_segments() {
+        let parse = |s: &str| s.parse::<PEP440Version>().unwrap();
+        assert!(parse("1.2.3.4") < parse("1.2.3.5"));
+        assert!(parse("1.2.3") < parse("1.2.3.1"));
+        assert!(parse("1.2.3.4") < parse("1.2.4"));
+        assert!(parse("2020.12.5") < parse("2020.12.5.1"));
+        assert_eq!(parse("1.2"), parse("1.2.0"));
+        assert_eq!(parse("1.2"), parse("1.2.0.0.0"));
+        assert_eq!(parse("1"), PEP440Version::one());
+        assert_ne!(parse("1.0.1"), parse("1.0.0.1"));
+    }
+
+    #[test]
+    fn hash_release_segments() {
+        let mut set = HashSet::new();
+        set.insert("1.2".parse::<PEP440Version>().unwrap());
+        assert!(set.contains(&"1.2.0".parse::<PEP440Version>().unwrap()));
+    }
+
+    #[test]
+    fn parse_implicit_numbers() {
+        let parse = |s: &str| s.parse::<PEP440Version>().unwrap();
+        assert_eq!(parse("1.0-1"), PEP440Version::one().bump_post().bump_post());
+        assert_eq!(parse("1.0.post"), PEP440Version::one().bump_post());
+        assert_eq!(parse("1.0.dev"), PEP440Version::one().bump_dev());
+        assert_eq!(
+            parse("1.0a"),
+            PEP440Version::one().pre_release(Prerelease::Alpha)
+        );
+        assert_eq!(parse("1.0-RC.2"), parse("1.0rc2"));
+        assert_eq!(parse("1.0c1"), parse("1.0rc1"));
+        assert_eq!(parse(" v1.0.REV3 "), parse("1.0.post3"));
+    }
+
+    #[test]
+    fn lowest_version() {
+        for version in ORDERED_VERSIONS {
+            assert!(PEP440Version::lowest_pep440() <= version.parse().unwrap());
+        }
+        assert!(PEP440Version::lowest_pep440() < "0a0".parse().unwrap());
+        assert_eq!(PEP440Version::lowest_pep440(), "0.dev0".parse().unwrap());
+    }
+
+    #[test]
+    fn parse_local() {
+        let version: PEP440Version = "1.7.0+cu110".parse().unwrap();
+        assert_eq!(
+            version.local,
+            vec![LocalSegment::Alphanumeric("cu110".into())]
+        );
+        let version: PEP440Version = "1.0+Ubuntu-1_2".parse().unwrap();
+        assert_eq!(
+            version.local,
+            vec![
+                LocalSegment::Alphanumeric("ubuntu".into()),
+                LocalSegment::Numeric(1),
+                LocalSegment::Numeric(2),
+            ]
+        );
+        assert_eq!(version.public(), "1.0".parse().unwrap());
+    }
+
+    #[test]
+    fn compare_local() {
+        let parse = |s: &str| s.parse::<PEP440Version>().unwrap();
+        assert_ne!(parse("1.7.0+cpu"), parse("1.7.0+cu110"));
+        assert_ne!(parse("1.7.0+cpu"), parse("1.7.0"));
+        assert_eq!(parse("1.7.0+cpu"), parse("1.7+CPU"));
+        assert_eq!(parse("1.0+01"), parse("1.0+1"));
+        assert!(parse("1.0+1") < parse("1.0+1.0"));
+    }
+
+    #[test]
+    fn format_local() {
+        assert_eq!(
+            format!("{}", "1.7.0+cu110".parse::<PEP440Version>().unwrap()),
+            "1.7.0+cu110"
+        );
+        assert_eq!(
+            format!("{}", "1.0+ubuntu-1".parse::<PEP440Version>().unwrap()),
+            "1.0+ubuntu.1"
+        );
+    }
+
+    #[test]
+    fn compare_arbitrary() {
+        let foobar = PEP440Version::from_arbitrary("foobar");
+        assert!(foobar < PEP440Version::lowest_pep440());
+        assert!(PEP440Version::lowest() < foobar);
+        assert!(foobar < foobar.bump());
+        assert!(foobar.bump() < PEP440Version::from_arbitrary("foobar1"));
+        assert_eq!(foobar, PEP440Version::from_arbitrary("FooBar"));
+        assert_ne!(PEP440Version::from_arbitrary("1.0"), PEP440Version::one());
+        assert_eq!(
+            format!("{}", PEP440Version::from_arbitrary("2004d")),
+            "2004d"
+        );
+    }
+
     #[test]
     fn format_version() {
         assert_eq!(format!("{}", PEP440Version::zero()), "0.0.0");
@@ -336,15 +752,54 @@ mod tests {
         );
         assert_eq!(
             format!("{}", PEP440Version::zero().bump_post()),
-            "0.0.0post0"
+            "0.0.0.post0"
+        );
+        assert_eq!(
+            format!("{}", PEP440Version::zero().bump_dev()),
+            "0.0.0.dev0"
         );
-        assert_eq!(format!("{}", PEP440Version::zero().bump_dev()), "0.0.0dev0");
+        let version: PEP440Version = "1.0post1dev2+cpu".parse().unwrap();
+        assert_eq!(version.to_string(), "1.0.post1.dev2+cpu");
     }
 
     #[test]
     fn bump_patch() {
         let version = PEP440Version::zero();
-        assert_eq!(version.bump(), version.bump_patch());
+        assert_eq!(version.bump_patch(), PEP440Version::new(0, 0, 1));
+    }
+
+    #[test]
+    fn bump_final() {
+        let version = PEP440Version::zero();
+        assert_eq!(version.bump_public(), "0.0.0.post0.dev0".parse().unwrap());
+    }
+
+    #[test]
+    fn bump_local() {
+        let version: PEP440Version = "1.7.0".parse().unwrap();
+        assert!(version < version.bump());
+        assert!(version.bump() < "1.7.0+a".parse().unwrap());
+        let version: PEP440Version = "1.7.0+cpu".parse().unwrap();
+        assert!(version < version.bump());
+        assert!(version.bump() < "1.7.0+cpu.0a".parse().unwrap());
+        assert!(version.bump() < "1.7.0+cu110".parse().unwrap());
+    }
+
+    #[test]
+    fn bump_public_skips_local() {
+        let version: PEP440Version = "1.7.0".parse().unwrap();
+        assert!("1.7.0+cu110".parse::<PEP440Version>().unwrap() < version.bump_public());
+        assert!(version.bump_public() < "1.7.0.post0".parse().unwrap());
+    }
+
+    #[test]
+    fn bump_post_releases_skips_post_releases() {
+        let version: PEP440Version = "1.7".parse().unwrap();
+        let bound = version.bump_post_releases();
+        assert!("1.7.post1+cu110".parse::<PEP440Version>().unwrap() < bound);
+        assert!("1.7.post99999".parse::<PEP440Version>().unwrap() < bound);
+        assert!(bound < "1.7.0.1.dev0".parse().unwrap());
+        assert!(bound < "1.7.1a1".parse().unwrap());
     }
 
     #[test]
@@ -353,6 +808,17 @@ mod tests {
         assert_eq!(version.bump_minor(), PEP440Version::new(0, 1, 0));
     }
 
+    #[test]
+    fn bump_release() {
+        let version: PEP440Version = "2020.12.5.1".parse().unwrap();
+        assert_eq!(version.bump_release(3), "2020.12.5.2".parse().unwrap());
+        assert_eq!(version.bump_minor(), "2020.13.5.1".parse().unwrap());
+        assert_eq!(
+            PEP440Version::from_release(vec![1]).bump_patch(),
+            PEP440Version::new(1, 0, 1)
+        );
+    }
+
     #[test]
     fn bump_major() {
         let version = PEP440Version::zero();
@@ -362,22 +828,23 @@ mod tests {
     #[test]
     fn bump_post() {
         let version = PEP440Version::zero().bump_post();
-        assert_eq!(version.bump(), version.bump_post())
+        assert_eq!(version.bump_public(), version.bump_post().bump_dev())
     }
 
     #[test]
     fn bump_dev() {
         let version = PEP440Version::zero().bump_dev();
         assert_eq!(
-            version.bump(),
+            version.bump_public(),
             PEP440Version {
-                major: 0,
-                minor: 0,
-                patch: 0,
                 epoch: 0,
+                release: vec![0, 0, 0],
                 pre: None,
                 post: None,
-                dev: Some(1)
+                dev: Some(1),
+                local: vec![],
+                arbitrary: None,
+                above_post_releases: false,
             }
         )
     }
@@ -386,16 +853,128 @@ mod tests {
     fn bump_post_dev() {
         let version = PEP440Version::zero().bump_post().bump_dev();
         assert_eq!(
-            version.bump(),
+            version.bump_public(),
             PEP440Version {
-                major: 0,
-                minor: 0,
-                patch: 0,
                 epoch: 0,
+                release: vec![0, 0, 0],
                 pre: None,
                 post: Some(0),
-                dev: Some(1)
+                dev: Some(1),
+                local: vec![],
+                arbitrary: None,
+                above_post_releases: false,
             }
         )
     }
+
+    /// Versions in strictly increasing order, taken from the `packaging` test suite.
+    const ORDERED_VERSIONS: &[&str] = &[
+        "0.dev0",
+        "0a0",
+        "0",
+        "1.0.dev456",
+        "1.0a1",
+        "1.0a2.dev456",
+        "1.0a12.dev456",
+        "1.0a12",
+        "1.0b1.dev456",
+        "1.0b2",
+        "1.0b2.post345.dev456",
+        "1.0b2.post345",
+        "1.0b2-346",
+        "1.0c1.dev456",
+        "1.0c1",
+        "1.0rc2",
+        "1.0c3",
+        "1.0",
+        "1.0.post456.dev34",
+        "1.0.post456",
+        "1.1.dev1",
+        "1.2",
+        "1.2+123abc",
+        "1.2+123abc456",
+        "1.2+abc",
+        "1.2+abc123",
+        "1.2+abc123def",
+        "1.2+1234.abc",
+        "1.2+123456",
+        "1.2.r32",
+        "1.2.r32+123456",
+        "1.2.rev33",
+        "1.2.rev33+123456",
+        "2020.1",
+        "1!1.0.dev456",
+        "1!1.0a1",
+        "1!1.0a2.dev456",
+        "1!1.0a12.dev456",
+        "1!1.0a12",
+        "1!1.0b1.dev456",
+        "1!1.0b2",
+        "1!1.0b2.post345.dev456",
+        "1!1.0b2.post345",
+        "1!1.0b2-346",
+        "1!1.0c1.dev456",
+        "1!1.0c1",
+        "1!1.0rc2",
+        "1!1.0c3",
+        "1!1.0",
+        "1!1.0.post456.dev34",
+        "1!1.0.post456",
+    ];
+
+    /// Equivalent spellings of a version from [ORDERED_VERSIONS].
+    fn spelling(version: &'static str) -> impl Strategy<Value = String> {
+        let separator = prop::sample::select(vec!["", ".", "-", "_"]);
+        let local_separator = prop::sample::select(vec![".", "-", "_"]);
+        (separator, local_separator, any::<bool>(), any::<bool>()).prop_map(
+            move |(sep, local_sep, upper, padded)| {
+                let mut spelled = version.replace(".dev", &format!("{}dev", sep));
+                spelled = spelled.replace(".post", &format!("{}post", sep));
+                spelled = spelled.replace(".r", &format!("{}r", sep));
+                if padded {
+                    let end = VERSION_PATTERN
+                        .captures(&spelled)
+                        .and_then(|c| c.name("release"))
+                        .unwrap()
+                        .end();
+                    spelled.insert_str(end, ".0");
+                }
+                if let Some(start) = spelled.find('+') {
+                    let local = spelled[start..].replace('.', local_sep);
+                    spelled.replace_range(start.., &local);
+                }
+                if upper {
+                    spelled = spelled.to_uppercase();
+                }
+                spelled
+            },
+        )
+    }
+
+    fn ordered_version() -> impl Strategy<Value = (usize, String)> {
+        (0..ORDERED_VERSIONS.len()).prop_flat_map(|i| (Just(i), spelling(ORDERED_VERSIONS[i])))
+    }
+
+    proptest! {
+        #[test]
+        fn ordering_matches_reference_table(
+            (i, left) in ordered_version(),
+            (j, right) in ordered_version(),
+        ) {
+            let left: PEP440Version = left.parse().unwrap();
+            let right: PEP440Version = right.parse().unwrap();
+            prop_assert_eq!(left.cmp(&right), i.cmp(&j), "{} vs {}", left, right);
+            prop_assert_eq!(left == right, i == j);
+        }
+
+        #[test]
+        fn bump_is_the_successor((i, version) in ordered_version()) {
+            let version: PEP440Version = version.parse().unwrap();
+            let bumped = version.bump();
+            prop_assert!(version < bumped);
+            for higher in &ORDERED_VERSIONS[i + 1..] {
+                prop_assert!(bumped <= higher.parse().unwrap());
+            }
+        }
+    }
 }